@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lapce_core::word::{get_char_property, CharClassification, WordCursor};
+use xi_rope::Rope;
+
+/// Mostly ascii source code, the common input of word motion
+fn source_text() -> String {
+    concat!(
+        "pub fn get_char_property(codepoint: char) -> CharClassification {\n",
+        "    // Fast path for ascii letters, the most common characters\n",
+        "    if codepoint.is_ascii_alphabetic() {\n",
+        "        return CharClassification::Other;\n",
+        "    }\n",
+        "}\n",
+    )
+    .repeat(200)
+}
+
+fn char_property(c: &mut Criterion) {
+    let text = source_text();
+    c.bench_function("get_char_property", |b| {
+        b.iter(|| {
+            text.chars()
+                .filter(|c| {
+                    get_char_property(black_box(*c)) == CharClassification::Other
+                })
+                .count()
+        })
+    });
+}
+
+fn word_motion(c: &mut Criterion) {
+    let rope = Rope::from(source_text());
+    c.bench_function("next_boundary", |b| {
+        b.iter(|| {
+            let mut cursor = WordCursor::new(&rope, 0);
+            while cursor.next_boundary().is_some() {}
+        })
+    });
+}
+
+criterion_group!(benches, char_property, word_motion);
+criterion_main!(benches);
@@ -1,3 +1,5 @@
+use xi_rope::Rope;
+
 /// Convert a utf8 offset into a utf16 offset, if possible  
 /// `text` is what the offsets are into
 pub fn offset_utf8_to_utf16(
@@ -45,6 +47,23 @@ pub fn offset_utf8_to_utf16_str(text: &str, offset: usize) -> usize {
     offset_utf8_to_utf16(text.char_indices(), offset)
 }
 
+/// Convert a utf8 offset of `text` into its line and the utf16 offset from the
+/// start of that line, as in an LSP `Position`
+pub fn offset_utf8_to_utf16_line_col(text: &Rope, offset: usize) -> (usize, usize) {
+    let line = text.line_of_offset(offset);
+    let line_offset = text.offset_of_line(line);
+    let mut chunk_offset = 0;
+    let char_indices =
+        text.iter_chunks(line_offset..offset)
+            .flat_map(move |chunk| {
+                let start = chunk_offset;
+                chunk_offset += chunk.len();
+                chunk.char_indices().map(move |(i, c)| (start + i, c))
+            });
+    let col = offset_utf8_to_utf16(char_indices, offset - line_offset);
+    (line, col)
+}
+
 /// Convert a utf16 offset into a utf8 offset, if possible  
 /// `char_indices` is an iterator over utf8 offsets and the characters
 /// It is cloneable so that it can be iterated multiple times. Though it should be cheaply cloneable.
@@ -99,7 +118,12 @@ pub fn offset_utf16_to_utf8_str(text: &str, offset: usize) -> usize {
 mod tests {
     // TODO: more tests with unicode characters
 
-    use crate::encoding::{offset_utf16_to_utf8_str, offset_utf8_to_utf16_str};
+    use xi_rope::Rope;
+
+    use crate::encoding::{
+        offset_utf16_to_utf8_str, offset_utf8_to_utf16_line_col,
+        offset_utf8_to_utf16_str,
+    };
 
     #[test]
     fn utf8_to_utf16() {
@@ -160,4 +184,15 @@ fn utf16_to_utf8() {
         assert_eq!(offset_utf16_to_utf8_str("×a", 1), 2);
         assert_eq!(offset_utf16_to_utf8_str("×a", 2), 3);
     }
+
+    #[test]
+    fn utf8_to_utf16_line_col() {
+        let text = Rope::from("a😀b\n😀c");
+
+        assert_eq!(offset_utf8_to_utf16_line_col(&text, 0), (0, 0));
+        assert_eq!(offset_utf8_to_utf16_line_col(&text, 5), (0, 3));
+        assert_eq!(offset_utf8_to_utf16_line_col(&text, 7), (1, 0));
+        assert_eq!(offset_utf8_to_utf16_line_col(&text, 11), (1, 2));
+        assert_eq!(offset_utf8_to_utf16_line_col(&text, text.len()), (1, 3));
+    }
 }
@@ -1,9 +1,16 @@
+use std::{borrow::Cow, collections::HashMap, fmt, ops::Range};
+
+use unicode_width::UnicodeWidthChar;
 use xi_rope::{Cursor, Rope, RopeInfo};
 
-use crate::syntax::util::{matching_char, matching_pair_direction};
+use crate::{
+    encoding::offset_utf8_to_utf16_line_col,
+    indent::IndentStyle,
+    syntax::util::{matching_char, matching_pair_direction},
+};
 
 /// Describe char classifications used to compose word boundaries
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CharClassification {
     /// Carriage Return (`r`)
     Cr,
@@ -13,10 +20,259 @@ pub enum CharClassification {
     Space,
     /// Any punctuation character
     Punctuation,
+    /// Control characters (ex: `NUL`, `BEL` or `DEL`), except line breaks and tabs
+    Control,
     /// Includes letters and all of non-ascii unicode
     Other,
 }
 
+impl CharClassification {
+    /// Name of the classification, for logging and diagnostics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CharClassification::Cr => "Cr",
+            CharClassification::Lf => "Lf",
+            CharClassification::Space => "Space",
+            CharClassification::Punctuation => "Punctuation",
+            CharClassification::Control => "Control",
+            CharClassification::Other => "Other",
+        }
+    }
+}
+
+impl fmt::Display for CharClassification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A matched bracket pair, see [`WordCursor::match_pairs_info`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MatchInfo {
+    /// Offset of the matching bracket
+    pub offset: usize,
+    /// The bracket under the cursor
+    pub bracket: char,
+    /// Number of lines spanned by the pair, `1` when both brackets are on the same line
+    pub lines: usize,
+}
+
+/// The word under the cursor, used to search for its occurrences, see [`word_query`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WordQuery {
+    /// Range of the word in the rope
+    pub range: Range<usize>,
+    /// Text of the word
+    pub text: String,
+    /// `true` for an identifier, `false` for a run of punctuation characters
+    pub is_identifier: bool,
+}
+
+/// The result of [`WordCursor::match_pairs_result`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MatchResult {
+    /// Offset of the bracket matching the one under the cursor
+    Matched(usize),
+    /// The character under the cursor is not a bracket
+    NotOnBracket,
+    /// The bracket under the cursor has no match in the rope
+    Unmatched,
+}
+
+/// Whether a bracket opens or closes a pair, see [`BracketCursor::bracket_role`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BracketRole {
+    /// Opening bracket (ex: `(`), matched forward
+    Open,
+    /// Closing bracket (ex: `)`), matched backward
+    Close,
+}
+
+/// A bracket with no counterpart, see [`unmatched_in`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnmatchedBracket {
+    /// Offset of an opening bracket that is never closed
+    Opening(usize),
+    /// Offset of a closing bracket that has no opening bracket
+    Closing(usize),
+}
+
+/// Summary of the brackets of a range, see [`bracket_health`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BracketHealth {
+    /// Number of bracket pairs opened and closed in the range
+    pub matched: usize,
+    /// Number of opening brackets that are never closed
+    pub unmatched_open: usize,
+    /// Number of closing brackets that have no opening bracket
+    pub unmatched_close: usize,
+}
+
+impl BracketHealth {
+    /// Whether every bracket of the range is matched
+    pub fn is_balanced(&self) -> bool {
+        self.unmatched_open == 0 && self.unmatched_close == 0
+    }
+}
+
+/// Cache of the bracket matches of each line, filled by
+/// [`BracketCursor::matches_for_line`] to avoid matching the brackets of the
+/// visible lines again on every scroll.
+///
+/// Cached offsets are absolute, so the cache must be told about every edit
+/// with [`BracketMatchCache::invalidate_line`], called with the first line
+/// touched by the edit. This drops the entries of that line and of all the
+/// following ones, whose offsets are shifted by the edit, as well as the entries
+/// of previous lines with a bracket matched on or after the edited line, or not
+/// matched at all. Entries of previous lines with all their brackets matched
+/// before the edited line are kept.
+#[derive(Clone, Debug, Default)]
+pub struct BracketMatchCache {
+    lines: HashMap<usize, LineBracketMatches>,
+}
+
+#[derive(Clone, Debug)]
+struct LineBracketMatches {
+    /// Offset of each bracket of the line, and of its matching bracket
+    matches: Vec<(usize, Option<usize>)>,
+    /// Last line holding a matching bracket, `usize::MAX` if a bracket is unmatched
+    reach: usize,
+}
+
+impl BracketMatchCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the matches of `line` are cached
+    pub fn contains(&self, line: usize) -> bool {
+        self.lines.contains_key(&line)
+    }
+
+    /// Invalidate the entries affected by an edit starting on `line`
+    pub fn invalidate_line(&mut self, line: usize) {
+        self.lines
+            .retain(|cached, entry| *cached < line && entry.reach < line);
+    }
+
+    /// Drop every entry, ex: when the whole document is replaced
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+/// The line ending used by a rope, see [`detect_line_ending`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r` alone
+    Cr,
+    /// Several kinds of line endings are used
+    Mixed,
+}
+
+/// A class of characters making up a [`TokenSpec`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    /// Unicode letters, see [`char::is_alphabetic`]
+    Letter,
+    /// ASCII digits `0-9`
+    Digit,
+    /// ASCII hexadecimal digits `0-9`, `a-f` and `A-F`
+    HexDigit,
+    /// A specific character
+    Char(char),
+}
+
+impl TokenClass {
+    fn contains(&self, c: char) -> bool {
+        match self {
+            TokenClass::Letter => c.is_alphabetic(),
+            TokenClass::Digit => c.is_ascii_digit(),
+            TokenClass::HexDigit => c.is_ascii_hexdigit(),
+            TokenClass::Char(other) => c == *other,
+        }
+    }
+}
+
+/// The characters a token is made of, as a union of [`TokenClass`], to find
+/// language specific tokens with [`token_at`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TokenSpec<'a> {
+    pub classes: &'a [TokenClass],
+}
+
+impl<'a> TokenSpec<'a> {
+    /// Letters, digits and underscores
+    pub const IDENTIFIER: TokenSpec<'static> = TokenSpec {
+        classes: &[TokenClass::Letter, TokenClass::Digit, TokenClass::Char('_')],
+    };
+    /// Digits, dots and `x` for hexadecimal prefixes
+    pub const NUMBER: TokenSpec<'static> = TokenSpec {
+        classes: &[
+            TokenClass::Digit,
+            TokenClass::Char('.'),
+            TokenClass::Char('x'),
+        ],
+    };
+
+    pub fn new(classes: &'a [TokenClass]) -> Self {
+        Self { classes }
+    }
+
+    /// Whether `c` can be part of the token
+    pub fn contains(&self, c: char) -> bool {
+        self.classes.iter().any(|class| class.contains(c))
+    }
+}
+
+/// The kind of content of a line, see [`line_kind`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    /// Empty or whitespace only line
+    Blank,
+    /// Line only containing a line comment
+    Comment,
+    /// Line containing code, possibly followed by a comment
+    Code,
+}
+
+/// The kind of a token yielded by [`classified_words`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WordKind {
+    /// Run of word characters not starting with a digit (ex: `foo_bar`)
+    Identifier,
+    /// Run of word characters starting with a digit, including a decimal point
+    /// followed by a digit (ex: `42`, `0xff` or `3.14`)
+    Number,
+    /// Run of punctuation characters other than brackets (ex: `+=`)
+    Operator,
+    /// Run of whitespace and line breaks
+    Whitespace,
+    /// A single bracket, or a run of control characters
+    Other,
+}
+
+/// The naming convention of an identifier, see [`identifier_style`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IdentifierStyle {
+    /// `fooBar`
+    CamelCase,
+    /// `FooBar`
+    PascalCase,
+    /// `foo_bar`, and single lowercase words
+    SnakeCase,
+    /// `FOO_BAR`, and single uppercase words
+    ScreamingSnake,
+    /// `foo-bar`
+    KebabCase,
+    /// Any other combination, ex: `foo_Bar`
+    Mixed,
+}
+
 /// A word boundary can be the start of a word, its end or both for punctuation
 #[derive(PartialEq, Eq)]
 enum WordBoundary {
@@ -39,26 +295,239 @@ fn is_end(&self) -> bool {
         *self == WordBoundary::End || *self == WordBoundary::Both
     }
 
-    #[allow(unused)]
     fn is_boundary(&self) -> bool {
         *self != WordBoundary::Interior
     }
 }
 
+/// Options changing how a [`WordCursor`] classifies characters
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WordOptions<'a> {
+    /// Classify non-ascii characters using their unicode properties,
+    /// see [`get_unicode_char_property`]
+    pub unicode: bool,
+    /// Stop code boundaries (and thus [`WordCursor::select_word`]) at line breaks,
+    /// including unicode line terminators even if `unicode` is not set
+    pub line_bounded: bool,
+    /// Ranges of string literals, each one is crossed in a single step by
+    /// [`WordCursor::next_boundary`] and [`WordCursor::prev_boundary`]
+    pub string_ranges: &'a [Range<usize>],
+    /// Make [`WordCursor::prev_deletion_boundary`] delete a whole indentation unit
+    /// when the cursor is inside the leading whitespace of a line
+    pub indent_style: Option<IndentStyle>,
+    /// Words never split by [`WordCursor::next_subword_boundary`] (ex: `macOS`)
+    pub compound_words: &'a [&'a str],
+    /// Treat a hyphen between two word characters as part of the word, so that
+    /// [`WordCursor::next_boundary`] and [`WordCursor::prev_boundary`] move over
+    /// hyphenated words (ex: `well-known`) in a single step
+    pub hyphenated_words: bool,
+    /// Separators treated as part of a number when found between two digits,
+    /// so that [`WordCursor::next_boundary`] and [`WordCursor::prev_boundary`]
+    /// move over thousands separated numbers (ex: `1,000,000`) in a single step
+    pub number_separators: &'a [char],
+    /// Make [`WordCursor::prev_deletion_boundary`] stop at subword boundaries
+    /// inside identifiers, like [`WordCursor::next_subword_boundary`], so that
+    /// deleting backward from `getHTTPResponse` leaves `getHTTP`
+    pub subword_deletion: bool,
+    /// Sorted offsets where lines are soft wrapped, each one being the start of a
+    /// display row, at which [`WordCursor::next_boundary`] and
+    /// [`WordCursor::prev_boundary`] stop (see [`display_row_range`])
+    pub wrap_points: &'a [usize],
+}
+
+/// The set of characters making up a word: [`CharClassification::Other`]
+/// characters, plus some extra characters (ex: `-` for css identifiers)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WordCharSet {
+    extra: Vec<char>,
+}
+
+impl WordCharSet {
+    pub fn new(extra: &[char]) -> Self {
+        Self {
+            extra: extra.to_vec(),
+        }
+    }
+
+    /// Whether the character is part of a word
+    pub fn contains(&self, c: char) -> bool {
+        get_char_property(c) == CharClassification::Other || self.extra.contains(&c)
+    }
+}
+
 /// A cursor providing utility function to navigate the rope
 /// by word boundaries.
 /// Boundaries can be the start of a word, its end, punctuation etc.
+///
+/// None of the methods panic on an empty rope, on a rope made of a single
+/// character, or with the cursor at the end of the rope. Methods returning an
+/// `Option` return `None` when there is no
+/// character to move over in their direction (ex: `next_boundary` at the end
+/// of the rope), methods returning an offset return the cursor position, and
+/// `select_word` returns an empty range when the cursor is neither on a word nor
+/// on punctuation.
 pub struct WordCursor<'a> {
     pub(crate) inner: Cursor<'a, RopeInfo>,
+    options: WordOptions<'a>,
 }
 
 impl<'a> WordCursor<'a> {
+    /// Create a cursor at `pos`. An offset which is not on a char boundary is
+    /// snapped to the start of the char containing it, and an offset past the end
+    /// of the rope to its end, see [`WordCursor::try_new`] to reject them instead.
     pub fn new(text: &'a Rope, pos: usize) -> WordCursor<'a> {
-        let inner = Cursor::new(text, pos);
-        WordCursor { inner }
+        Self::with_options(text, pos, WordOptions::default())
+    }
+
+    /// Create a cursor at `pos`, or return `None` if `pos` is past the end of the
+    /// rope or is not on a char boundary.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("héllo");
+    /// assert!(WordCursor::try_new(&rope, 1).is_some());
+    /// assert!(WordCursor::try_new(&rope, 2).is_none());
+    ///```
+    pub fn try_new(text: &'a Rope, pos: usize) -> Option<WordCursor<'a>> {
+        if !is_char_boundary(text, pos) {
+            return None;
+        }
+        Some(Self::new(text, pos))
+    }
+
+    /// Build a rope from `text` and return it owned along with the cursor position,
+    /// for tests and callers only having a `&str`. Word cursors are then created
+    /// from it with [`OwnedCursor::word_cursor`].
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// let owned = WordCursor::from_str("Hello world", 0);
+    /// assert_eq!(owned.word_cursor().next_boundary(), Some(6));
+    ///```
+    pub fn from_str(text: &str, pos: usize) -> OwnedCursor {
+        OwnedCursor::new(text, pos)
+    }
+
+    /// Create a cursor using the given [`WordOptions`].
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::{WordCursor, WordOptions};
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("Hello\u{a0}world");
+    /// let options = WordOptions { unicode: true, ..Default::default() };
+    /// let mut cursor = WordCursor::with_options(&rope, 0, options);
+    /// assert_eq!(cursor.next_boundary(), Some(7));
+    ///```
+    pub fn with_options(
+        text: &'a Rope,
+        pos: usize,
+        options: WordOptions<'a>,
+    ) -> WordCursor<'a> {
+        let inner = Cursor::new(text, snap_to_char_boundary(text, pos));
+        WordCursor { inner, options }
+    }
+
+    /// Set the words that [`WordCursor::next_subword_boundary`] should not split.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("macOSVersion");
+    /// let mut cursor = WordCursor::new(&rope, 0).with_compound_dictionary(&["macOS"]);
+    /// assert_eq!(cursor.next_subword_boundary(), Some(5));
+    ///```
+    pub fn with_compound_dictionary(mut self, words: &'a [&'a str]) -> Self {
+        self.options.compound_words = words;
+        self
+    }
+
+    /// Classify a character according to the cursor options
+    fn char_property(&self, codepoint: char) -> CharClassification {
+        if self.options.unicode {
+            get_unicode_char_property(codepoint)
+        } else {
+            get_char_property(codepoint)
+        }
+    }
+
+    /// Classify the character located at `offset`, characters of string literals
+    /// are all considered as word characters so that a string is a single word,
+    /// and so are hyphens inside hyphenated words and number separators between
+    /// digits when enabled
+    fn char_property_at(
+        &self,
+        codepoint: char,
+        offset: usize,
+    ) -> CharClassification {
+        if self
+            .options
+            .string_ranges
+            .iter()
+            .any(|range| range.contains(&offset))
+            || (codepoint == '-' && self.is_word_hyphen(offset))
+            || self.is_number_separator(codepoint, offset)
+        {
+            CharClassification::Other
+        } else {
+            self.char_property(codepoint)
+        }
+    }
+
+    /// Whether a display row starts at `offset`, see [`WordOptions::wrap_points`]
+    fn is_wrap_point(&self, offset: usize) -> bool {
+        self.options.wrap_points.binary_search(&offset).is_ok()
+    }
+
+    /// Whether the hyphen at `offset` joins two words, such as in `well-known`
+    fn is_word_hyphen(&self, offset: usize) -> bool {
+        if !self.options.hyphenated_words {
+            return false;
+        }
+        let text = self.inner.root();
+        let is_word_char = |c: Option<char>| {
+            c.map(|c| self.char_property(c)) == Some(CharClassification::Other)
+        };
+        is_word_char(Cursor::new(text, offset).prev_codepoint())
+            && is_word_char(Cursor::new(text, offset + 1).peek_next_codepoint())
+    }
+
+    /// Whether `codepoint` at `offset` is a number separator between two digits,
+    /// such as in `1,000`
+    fn is_number_separator(&self, codepoint: char, offset: usize) -> bool {
+        if !self.options.number_separators.contains(&codepoint) {
+            return false;
+        }
+        let text = self.inner.root();
+        let is_digit = |c: Option<char>| c.map_or(false, |c| c.is_ascii_digit());
+        is_digit(Cursor::new(text, offset).prev_codepoint())
+            && is_digit(
+                Cursor::new(text, offset + codepoint.len_utf8())
+                    .peek_next_codepoint(),
+            )
+    }
+
+    /// Whether the character is a line break the cursor should stop at
+    fn is_line_break(&self, codepoint: char) -> bool {
+        self.options.line_bounded
+            && matches!(
+                get_unicode_char_property(codepoint),
+                CharClassification::Cr | CharClassification::Lf
+            )
     }
 
     /// Get the previous start boundary of a word, and set the cursor position to the boundary found.
+    /// The start of the rope counts as a boundary, so `None` is only returned when
+    /// the cursor is at the start of the rope.
+    ///
     /// **Example:**
     ///
     /// ```rust
@@ -71,11 +540,13 @@ pub fn new(text: &'a Rope, pos: usize) -> WordCursor<'a> {
     ///```
     pub fn prev_boundary(&mut self) -> Option<usize> {
         if let Some(ch) = self.inner.prev_codepoint() {
-            let mut prop = get_char_property(ch);
+            let mut prop = self.char_property_at(ch, self.inner.pos());
             let mut candidate = self.inner.pos();
             while let Some(prev) = self.inner.prev_codepoint() {
-                let prop_prev = get_char_property(prev);
-                if classify_boundary(prop_prev, prop).is_start() {
+                let prop_prev = self.char_property_at(prev, self.inner.pos());
+                if classify_boundary(prop_prev, prop).is_start()
+                    || self.is_wrap_point(candidate)
+                {
                     break;
                 }
                 prop = prop_prev;
@@ -104,15 +575,22 @@ pub fn prev_boundary(&mut self) -> Option<usize> {
     /// assert_eq!(&text[..position.unwrap()], "violet ");
     ///```
     pub fn prev_deletion_boundary(&mut self) -> Option<usize> {
-        if let Some(ch) = self.inner.prev_codepoint() {
-            let mut prop = get_char_property(ch);
+        if let Some(indent_style) = self.options.indent_style {
+            if let Some(candidate) = self.prev_indent_boundary(indent_style) {
+                self.inner.set(candidate);
+                return Some(candidate);
+            }
+        }
+        let mut next = self.inner.peek_next_codepoint();
+        if let Some(mut ch) = self.inner.prev_codepoint() {
+            let mut prop = self.char_property(ch);
             let mut candidate = self.inner.pos();
 
             // Flag, determines if the word should be deleted or not
             // If not, erase only whitespace characters.
             let mut keep_word = false;
             while let Some(prev) = self.inner.prev_codepoint() {
-                let prop_prev = get_char_property(prev);
+                let prop_prev = self.char_property(prev);
 
                 // Stop if line beginning reached, without any non-whitespace characters
                 if prop_prev == CharClassification::Lf
@@ -141,12 +619,23 @@ pub fn prev_deletion_boundary(&mut self) -> Option<usize> {
                     break;
                 }
 
+                // Subword deletion inside identifiers
+                if self.options.subword_deletion
+                    && prop_prev == CharClassification::Other
+                    && prop == CharClassification::Other
+                    && self.is_subword_boundary(prev, ch, next, candidate)
+                {
+                    break;
+                }
+
                 // Default deletion
                 if classify_boundary(prop_prev, prop).is_start() {
                     break;
                 }
                 prop = prop_prev;
                 candidate = self.inner.pos();
+                next = Some(ch);
+                ch = prev;
             }
             self.inner.set(candidate);
             return Some(candidate);
@@ -154,6 +643,38 @@ pub fn prev_deletion_boundary(&mut self) -> Option<usize> {
         None
     }
 
+    /// If the cursor is inside the leading whitespace of a line, return the
+    /// position one indentation unit before it
+    fn prev_indent_boundary(&self, indent_style: IndentStyle) -> Option<usize> {
+        let pos = self.inner.pos();
+        let text = self.inner.root();
+        let indent = line_indent(text, pos);
+        if pos == indent.start || pos > indent.end {
+            return None;
+        }
+        let mut cursor = Cursor::new(text, pos);
+        if cursor.prev_codepoint() == Some('\t') {
+            return Some(pos - 1);
+        }
+        let spaces = text
+            .slice_to_cow(indent.start..pos)
+            .chars()
+            .rev()
+            .take_while(|c| *c == ' ')
+            .count();
+        let remove = match indent_style {
+            IndentStyle::Tabs => spaces,
+            IndentStyle::Spaces(width) => {
+                let width = width.max(1) as usize;
+                let col = pos - indent.start;
+                let r = col % width;
+                let r = if r == 0 { width } else { r };
+                r.min(spaces)
+            }
+        };
+        Some(pos - remove)
+    }
+
     /// Get the position of the next non blank character in the rope
     ///
     /// **Example:**
@@ -169,7 +690,7 @@ pub fn prev_deletion_boundary(&mut self) -> Option<usize> {
     pub fn next_non_blank_char(&mut self) -> usize {
         let mut candidate = self.inner.pos();
         while let Some(next) = self.inner.next_codepoint() {
-            let prop = get_char_property(next);
+            let prop = self.char_property(next);
             if prop != CharClassification::Space {
                 break;
             }
@@ -179,7 +700,38 @@ pub fn next_non_blank_char(&mut self) -> usize {
         candidate
     }
 
+    /// Get the position of the next non blank character in the rope, skipping line
+    /// breaks unlike [`WordCursor::next_non_blank_char`], and whether a line feed was
+    /// crossed to reach it. The cursor position is set to the character found.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{  \n    world");
+    /// let mut cursor = WordCursor::new(&rope, 1);
+    /// assert_eq!(cursor.next_non_blank_char_info(), (8, true));
+    ///```
+    pub fn next_non_blank_char_info(&mut self) -> (usize, bool) {
+        let mut candidate = self.inner.pos();
+        let mut crossed_line = false;
+        while let Some(next) = self.inner.next_codepoint() {
+            match self.char_property(next) {
+                CharClassification::Lf => crossed_line = true,
+                CharClassification::Space | CharClassification::Cr => {}
+                _ => break,
+            }
+            candidate = self.inner.pos();
+        }
+        self.inner.set(candidate);
+        (candidate, crossed_line)
+    }
+
     /// Get the next start boundary of a word, and set the cursor position to the boundary found.
+    /// The end of the rope counts as a boundary, so `None` is only returned when
+    /// the cursor is at the end of the rope.
+    ///
     /// **Example:**
     ///
     /// ```rust
@@ -192,11 +744,15 @@ pub fn next_non_blank_char(&mut self) -> usize {
     ///```
     pub fn next_boundary(&mut self) -> Option<usize> {
         if let Some(ch) = self.inner.next_codepoint() {
-            let mut prop = get_char_property(ch);
+            let mut prop =
+                self.char_property_at(ch, self.inner.pos() - ch.len_utf8());
             let mut candidate = self.inner.pos();
             while let Some(next) = self.inner.next_codepoint() {
-                let prop_next = get_char_property(next);
-                if classify_boundary(prop, prop_next).is_start() {
+                let prop_next =
+                    self.char_property_at(next, self.inner.pos() - next.len_utf8());
+                if classify_boundary(prop, prop_next).is_start()
+                    || self.is_wrap_point(candidate)
+                {
                     break;
                 }
                 prop = prop_next;
@@ -208,7 +764,226 @@ pub fn next_boundary(&mut self) -> Option<usize> {
         None
     }
 
+    /// Same as [`WordCursor::next_boundary`], but also return the classification of
+    /// the character starting at the boundary. At the end of the rope, which has no
+    /// character, the classification is [`CharClassification::Space`].
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::{CharClassification, WordCursor};
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("Hello world");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// let (boundary, class) = cursor.next_boundary_classified().unwrap();
+    /// assert_eq!(boundary, 6);
+    /// assert_eq!(class, CharClassification::Other);
+    ///```
+    pub fn next_boundary_classified(
+        &mut self,
+    ) -> Option<(usize, CharClassification)> {
+        let boundary = self.next_boundary()?;
+        let class = match self.inner.peek_next_codepoint() {
+            Some(c) => self.char_property_at(c, boundary),
+            None => CharClassification::Space,
+        };
+        Some((boundary, class))
+    }
+
+    /// Same as [`WordCursor::next_boundary`], but return the boundary as a line and
+    /// an offset in UTF-16 code units from the start of that line, the `line` and
+    /// `character` of an LSP `Position`. The cursor position itself stays a byte
+    /// offset.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("a\n😀 world");
+    /// let mut cursor = WordCursor::new(&rope, 2);
+    /// assert_eq!(cursor.next_boundary_utf16(), Some((1, 3)));
+    ///```
+    pub fn next_boundary_utf16(&mut self) -> Option<(usize, usize)> {
+        let boundary = self.next_boundary()?;
+        Some(offset_utf8_to_utf16_line_col(self.inner.root(), boundary))
+    }
+
+    /// Same as [`WordCursor::prev_boundary`], but return the boundary as a line and
+    /// a UTF-16 offset in that line, see [`WordCursor::next_boundary_utf16`].
+    pub fn prev_boundary_utf16(&mut self) -> Option<(usize, usize)> {
+        let boundary = self.prev_boundary()?;
+        Some(offset_utf8_to_utf16_line_col(self.inner.root(), boundary))
+    }
+
+    /// Move over the next word along with the punctuation directly following it
+    /// (ex: `foo,` or `foo)`) in a single step, like Ctrl+Right in most GUI editors,
+    /// and set the cursor position to the offset reached. Leading whitespace is
+    /// skipped, and control characters are moved over like punctuation. Return
+    /// `None` at the end of the rope.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("foo, bar");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// assert_eq!(cursor.next_visual_word(), Some(4));
+    /// assert_eq!(cursor.next_visual_word(), Some(8));
+    ///```
+    pub fn next_visual_word(&mut self) -> Option<usize> {
+        self.inner.peek_next_codepoint()?;
+        for class in [
+            CharClassification::Space,
+            CharClassification::Other,
+            CharClassification::Punctuation,
+        ] {
+            while let Some(c) = self.inner.peek_next_codepoint() {
+                let prop = match self.char_property(c) {
+                    CharClassification::Lf | CharClassification::Cr => {
+                        CharClassification::Space
+                    }
+                    CharClassification::Control => CharClassification::Punctuation,
+                    prop => prop,
+                };
+                if prop != class {
+                    break;
+                }
+                self.inner.next_codepoint();
+            }
+        }
+        Some(self.inner.pos())
+    }
+
+    /// Same as [`WordCursor::next_boundary`], tuned for prose: commas, semicolons and
+    /// parentheses are part of the surrounding words, and `.`, `!` and `?` only
+    /// stop the cursor when they end a sentence, that is when followed by a space
+    /// or the end of the rope (ex: not in `e.g` or `3.14`).
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("Yes, no. Maybe");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// assert_eq!(cursor.next_prose_boundary(), Some(5));
+    /// assert_eq!(cursor.next_prose_boundary(), Some(7));
+    /// assert_eq!(cursor.next_prose_boundary(), Some(9));
+    ///```
+    pub fn next_prose_boundary(&mut self) -> Option<usize> {
+        let ch = self.inner.next_codepoint()?;
+        let mut prop = self.prose_property(ch);
+        let mut candidate = self.inner.pos();
+        while let Some(next) = self.inner.next_codepoint() {
+            let prop_next = self.prose_property(next);
+            if classify_boundary(prop, prop_next).is_start() {
+                break;
+            }
+            prop = prop_next;
+            candidate = self.inner.pos();
+        }
+        self.inner.set(candidate);
+        Some(candidate)
+    }
+
+    /// Classify `codepoint`, which the cursor was just moved over, for
+    /// [`WordCursor::next_prose_boundary`]
+    fn prose_property(&mut self, codepoint: char) -> CharClassification {
+        match codepoint {
+            ',' | ';' | '(' | ')' => CharClassification::Other,
+            '.' | '!' | '?' => match self.inner.peek_next_codepoint() {
+                Some(next)
+                    if !next.is_whitespace() && !matches!(next, '.' | '!' | '?') =>
+                {
+                    CharClassification::Other
+                }
+                _ => CharClassification::Punctuation,
+            },
+            _ => self.char_property_at(
+                codepoint,
+                self.inner.pos() - codepoint.len_utf8(),
+            ),
+        }
+    }
+
+    /// Get the next start boundary of a subword, and set the cursor position to the
+    /// boundary found. Besides word boundaries, subwords start after an `_` and at
+    /// case and digit transitions inside identifiers (see [`split_identifier`]),
+    /// except inside the words of the compound dictionary.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("getHTTPResponse code");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// assert_eq!(cursor.next_subword_boundary(), Some(3));
+    /// assert_eq!(cursor.next_subword_boundary(), Some(7));
+    /// assert_eq!(cursor.next_subword_boundary(), Some(16));
+    ///```
+    pub fn next_subword_boundary(&mut self) -> Option<usize> {
+        let mut prev = self.inner.next_codepoint()?;
+        loop {
+            let pos = self.inner.pos();
+            let c = match self.inner.next_codepoint() {
+                Some(c) => c,
+                None => return Some(pos),
+            };
+            let prop_prev = self.char_property(prev);
+            let prop = self.char_property(c);
+            let is_boundary = if prop_prev == CharClassification::Other
+                && prop == CharClassification::Other
+            {
+                let next = self.inner.peek_next_codepoint();
+                self.is_subword_boundary(prev, c, next, pos)
+            } else {
+                classify_boundary(prop_prev, prop).is_start()
+            };
+            if is_boundary {
+                self.inner.set(pos);
+                return Some(pos);
+            }
+            prev = c;
+        }
+    }
+
+    /// Whether a subword starts at `pos`, between `prev` and `c`, inside an
+    /// identifier. `next` is the character following `c`.
+    fn is_subword_boundary(
+        &self,
+        prev: char,
+        c: char,
+        next: Option<char>,
+        pos: usize,
+    ) -> bool {
+        !is_subword_separator(c)
+            && (is_subword_separator(prev)
+                || (is_subword_start(prev, c, next) && !self.in_compound_word(pos)))
+    }
+
+    /// Whether `pos` is strictly inside a word of the compound dictionary
+    fn in_compound_word(&self, pos: usize) -> bool {
+        if self.options.compound_words.is_empty() {
+            return false;
+        }
+        let text = self.inner.root();
+        let (start, end) = WordCursor::new(text, pos).select_code_word();
+        let word = text.slice_to_cow(start..end);
+        self.options.compound_words.iter().any(|compound| {
+            word.match_indices(*compound)
+                .any(|(i, _)| start + i < pos && pos < start + i + compound.len())
+        })
+    }
+
     /// Get the next end boundary, and set the cursor position to the boundary found.
+    /// The character under the cursor is skipped first, so that a cursor already at
+    /// the end of a word moves to the end of the next one, or to the end of the rope
+    /// when on its last character. `None` is only returned when the cursor is at
+    /// the end of the rope.
+    ///
     /// **Example:**
     ///
     /// ```rust
@@ -220,12 +995,12 @@ pub fn next_boundary(&mut self) -> Option<usize> {
     /// assert_eq!(end_boundary, Some(5));
     ///```
     pub fn end_boundary(&mut self) -> Option<usize> {
-        self.inner.next_codepoint();
+        self.inner.next_codepoint()?;
         if let Some(ch) = self.inner.next_codepoint() {
-            let mut prop = get_char_property(ch);
+            let mut prop = self.char_property(ch);
             let mut candidate = self.inner.pos();
             while let Some(next) = self.inner.next_codepoint() {
-                let prop_next = get_char_property(next);
+                let prop_next = self.char_property(next);
                 if classify_boundary(prop, prop_next).is_end() {
                     break;
                 }
@@ -235,7 +1010,7 @@ pub fn end_boundary(&mut self) -> Option<usize> {
             self.inner.set(candidate);
             return Some(candidate);
         }
-        None
+        Some(self.inner.pos())
     }
 
     /// Get the first matching [`CharClassification::Other`] backward and set the cursor position to this location .
@@ -253,8 +1028,8 @@ pub fn end_boundary(&mut self) -> Option<usize> {
     pub fn prev_code_boundary(&mut self) -> usize {
         let mut candidate = self.inner.pos();
         while let Some(prev) = self.inner.prev_codepoint() {
-            let prop_prev = get_char_property(prev);
-            if prop_prev != CharClassification::Other {
+            let prop_prev = self.char_property(prev);
+            if prop_prev != CharClassification::Other || self.is_line_break(prev) {
                 break;
             }
             candidate = self.inner.pos();
@@ -277,8 +1052,8 @@ pub fn prev_code_boundary(&mut self) -> usize {
     pub fn next_code_boundary(&mut self) -> usize {
         let mut candidate = self.inner.pos();
         while let Some(prev) = self.inner.next_codepoint() {
-            let prop_prev = get_char_property(prev);
-            if prop_prev != CharClassification::Other {
+            let prop_prev = self.char_property(prev);
+            if prop_prev != CharClassification::Other || self.is_line_break(prev) {
                 break;
             }
             candidate = self.inner.pos();
@@ -302,29 +1077,250 @@ pub fn next_code_boundary(&mut self) -> usize {
     /// assert_eq!(position, Some(0));
     ///```
     pub fn match_pairs(&mut self) -> Option<usize> {
-        let c = self.inner.peek_next_codepoint()?;
-        let other = matching_char(c)?;
-        let left = matching_pair_direction(other)?;
-        if left {
+        match self.match_pairs_result() {
+            MatchResult::Matched(offset) => Some(offset),
+            MatchResult::NotOnBracket | MatchResult::Unmatched => None,
+        }
+    }
+
+    /// Same as [`WordCursor::match_pairs`], but tell apart a cursor that is not on
+    /// a bracket from a bracket without a match, ex: to report unmatched brackets.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::{MatchResult, WordCursor};
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{ a");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// assert_eq!(cursor.match_pairs_result(), MatchResult::Unmatched);
+    /// let mut cursor = WordCursor::new(&rope, 2);
+    /// assert_eq!(cursor.match_pairs_result(), MatchResult::NotOnBracket);
+    ///```
+    pub fn match_pairs_result(&mut self) -> MatchResult {
+        let other = match self.inner.peek_next_codepoint().and_then(matching_char) {
+            Some(other) => other,
+            None => return MatchResult::NotOnBracket,
+        };
+        let left = match matching_pair_direction(other) {
+            Some(left) => left,
+            None => return MatchResult::NotOnBracket,
+        };
+        let offset = if left {
             self.previous_unmatched(other)
         } else {
             self.inner.next_codepoint();
-            let offset = self.next_unmatched(other)?;
-            Some(offset - 1)
+            self.next_unmatched(other).map(|offset| offset - 1)
+        };
+        match offset {
+            Some(offset) => MatchResult::Matched(offset),
+            None => MatchResult::Unmatched,
         }
     }
 
-    /// Take a matchable character and look cforward for the first unmatched one
-    /// ignoring the encountered matched pairs.
+    /// Same as [`WordCursor::match_pairs`], but also return the bracket under the
+    /// cursor and the number of lines spanned by the pair, so that callers can
+    /// behave differently for inline and multiline pairs.
+    ///
+    /// **Example:**
     ///
-    /// **Example**:
     /// ```rust
-    /// # use xi_rope::Rope;
     /// # use lapce_core::word::WordCursor;
-    /// let rope = Rope::from("outer {inner}} world");
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{\n  a\n}");
     /// let mut cursor = WordCursor::new(&rope, 0);
-    /// let position = cursor.next_unmatched('}');
-    /// assert_eq!(position, Some(14));
+    /// let info = cursor.match_pairs_info().unwrap();
+    /// assert_eq!(info.offset, 6);
+    /// assert_eq!(info.bracket, '{');
+    /// assert_eq!(info.lines, 3);
+    ///```
+    pub fn match_pairs_info(&mut self) -> Option<MatchInfo> {
+        let start = self.inner.pos();
+        let bracket = self.inner.peek_next_codepoint()?;
+        let offset = self.match_pairs()?;
+        let text = self.inner.root();
+        let lines = text
+            .slice_to_cow(start.min(offset)..start.max(offset))
+            .matches('\n')
+            .count()
+            + 1;
+        Some(MatchInfo {
+            offset,
+            bracket,
+            lines,
+        })
+    }
+
+    /// Return the offsets of the opening and of the closing bracket of the pair
+    /// under the cursor, in this order, whichever of the two brackets the cursor is
+    /// on. The cursor position is left unchanged.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("a(b)");
+    /// assert_eq!(WordCursor::new(&rope, 3).matched_range(), Some((1, 3)));
+    ///```
+    pub fn matched_range(&mut self) -> Option<(usize, usize)> {
+        let start = self.inner.pos();
+        let other = self.match_pairs();
+        self.inner.set(start);
+        let other = other?;
+        Some((start.min(other), start.max(other)))
+    }
+
+    /// Return the range to delete to remove the pair under the cursor along with its
+    /// content, closing bracket included, ready to be used with rope edits.
+    /// The cursor position is left unchanged.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{abc}");
+    /// assert_eq!(WordCursor::new(&rope, 0).delete_match_range(), Some(0..5));
+    ///```
+    pub fn delete_match_range(&mut self) -> Option<Range<usize>> {
+        let (open, close) = self.matched_range()?;
+        let text = self.inner.root();
+        let end = Cursor::new(text, close)
+            .next_codepoint()
+            .map_or(close, |c| close + c.len_utf8());
+        Some(open..end)
+    }
+
+    /// Return the text of the pair under the cursor, both brackets included, see
+    /// [`WordCursor::matched_range`]. The text is borrowed from the rope when it
+    /// lies in a single chunk, and allocated otherwise.
+    /// The cursor position is left unchanged.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("f(a, b);");
+    /// let mut cursor = WordCursor::new(&rope, 1);
+    /// assert_eq!(cursor.match_pairs_text().unwrap(), "(a, b)");
+    ///```
+    pub fn match_pairs_text(&mut self) -> Option<Cow<'a, str>> {
+        let range = self.delete_match_range()?;
+        Some(self.inner.root().slice_to_cow(range))
+    }
+
+    /// Same as [`WordCursor::match_pairs`], but return the line of the matching
+    /// bracket instead of its offset, for gutter rendering.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{\n  a\n}");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// assert_eq!(cursor.match_pairs_line(), Some(2));
+    ///```
+    pub fn match_pairs_line(&mut self) -> Option<usize> {
+        let offset = self.match_pairs()?;
+        Some(self.inner.root().line_of_offset(offset))
+    }
+
+    /// If the cursor is on an opening bracket, return the range to fold to hide the
+    /// pair content while keeping the line of the opening bracket visible: from the
+    /// end of that line to the closing bracket. Return `None` when both brackets
+    /// are on the same line, as there is nothing to fold. The cursor position is
+    /// left unchanged.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let text = "fn a() {\n    b();\n}";
+    /// let rope = Rope::from(text);
+    /// let mut cursor = WordCursor::new(&rope, 7);
+    /// assert_eq!(cursor.fold_range(), Some(8..18));
+    ///```
+    pub fn fold_range(&mut self) -> Option<Range<usize>> {
+        let start = self.inner.pos();
+        let bracket = self.inner.peek_next_codepoint()?;
+        if matching_pair_direction(bracket) != Some(true) {
+            return None;
+        }
+        let close = self.match_pairs();
+        self.inner.set(start);
+        let close = close?;
+        let text = self.inner.root();
+        let line = text.line_of_offset(start);
+        if text.line_of_offset(close) == line {
+            return None;
+        }
+        Some(line_bounds(text, line).1..close)
+    }
+
+    /// Return the positions of the innermost bracket pair of any kind enclosing the
+    /// cursor. Brackets of all kinds are tracked on a single stack, so that
+    /// correctly nested pairs are skipped as a whole. `None` is returned if the
+    /// nearest unmatched brackets around the cursor are of different kinds
+    /// (ex: `{ [ x } ]`), or if a bracket between them closes a bracket of another
+    /// kind (ex: the `( ]` of `{ ( ] x }`). The cursor position is left unchanged.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("( { x } )");
+    /// let mut cursor = WordCursor::new(&rope, 4);
+    /// assert_eq!(cursor.enclosing_pair(), Some((2, 6)));
+    ///```
+    pub fn enclosing_pair(&mut self) -> Option<(usize, usize)> {
+        let text = self.inner.root();
+        let pos = self.inner.pos();
+        let (start, open) = unmatched_bracket(text, pos, false, &DEFAULT_PAIRS)?;
+        let (end, close) = unmatched_bracket(text, pos, true, &DEFAULT_PAIRS)?;
+        (matching_char(open) == Some(close)).then_some((start, end))
+    }
+
+    /// Return the positions of the `open` bracket enclosing the cursor and of its
+    /// matching closing bracket, ignoring any other kind of bracket.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{ ( x ) }");
+    /// let mut cursor = WordCursor::new(&rope, 4);
+    /// assert_eq!(cursor.enclosing_pair_of('{'), Some((0, 8)));
+    ///```
+    pub fn enclosing_pair_of(&mut self, open: char) -> Option<(usize, usize)> {
+        if !matching_pair_direction(open)? {
+            return None;
+        }
+        let close = matching_char(open)?;
+        let pos = self.inner.pos();
+        let start = self.previous_unmatched(open);
+        self.inner.set(pos);
+        let end = self.next_unmatched(close);
+        self.inner.set(pos);
+        Some((start?, end? - close.len_utf8()))
+    }
+
+    /// Take a matchable character and look cforward for the first unmatched one
+    /// ignoring the encountered matched pairs.
+    ///
+    /// **Example**:
+    /// ```rust
+    /// # use xi_rope::Rope;
+    /// # use lapce_core::word::WordCursor;
+    /// let rope = Rope::from("outer {inner}} world");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// let position = cursor.next_unmatched('}');
+    /// assert_eq!(position, Some(14));
     ///  ```
     pub fn next_unmatched(&mut self, c: char) -> Option<usize> {
         let other = matching_char(c)?;
@@ -372,6 +1368,8 @@ pub fn previous_unmatched(&mut self, c: char) -> Option<usize> {
     }
 
     /// Return the previous and end boundaries of the word under cursor.
+    /// When the cursor is not on a word but on punctuation, the run of identical
+    /// punctuation characters under the cursor is selected instead (ex: `====`).
     ///
     /// **Example**:
     ///
@@ -385,225 +1383,6247 @@ pub fn previous_unmatched(&mut self, c: char) -> Option<usize> {
     /// assert_eq!(&text[start..end], "are");
     ///```
     pub fn select_word(&mut self) -> (usize, usize) {
+        // Select the first word rather than a leading byte order mark
+        if self.inner.pos() == 0
+            && self.inner.peek_next_codepoint() == Some('\u{feff}')
+        {
+            self.inner.next_codepoint();
+        }
+        let initial = self.inner.pos();
+        let (start, end) = self.select_code_word();
+        if start == end {
+            self.inner.set(initial);
+            if let Some(c) = self.inner.peek_next_codepoint() {
+                if self.char_property(c) == CharClassification::Punctuation {
+                    return self.scan_around(initial, |other| other == c);
+                }
+            }
+        }
+        (start, end)
+    }
+
+    /// Return the boundaries of the run of [`CharClassification::Other`] characters
+    /// around the cursor, which is empty when the cursor is not on a word
+    fn select_code_word(&mut self) -> (usize, usize) {
         let initial = self.inner.pos();
         let end = self.next_code_boundary();
         self.inner.set(initial);
         let start = self.prev_code_boundary();
         (start, end)
     }
-}
 
-/// Return the [`CharClassification`] of the input character
-pub fn get_char_property(codepoint: char) -> CharClassification {
-    if codepoint <= ' ' {
-        if codepoint == '\r' {
-            return CharClassification::Cr;
+    /// Return the boundaries of the "big word" under cursor: the run of non
+    /// whitespace characters, punctuation included. If the cursor is surrounded by
+    /// whitespace, the run of whitespace is returned instead.
+    ///
+    /// **Example**:
+    ///
+    ///```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let text = "let x = foo.bar();";
+    /// let rope = Rope::from(text);
+    /// let mut cursor = WordCursor::new(&rope, 12);
+    /// let (start, end) = cursor.select_big_word();
+    /// assert_eq!(&text[start..end], "foo.bar();");
+    ///```
+    pub fn select_big_word(&mut self) -> (usize, usize) {
+        let initial = self.inner.pos();
+        let is_big_word_char = |c| {
+            !matches!(
+                get_char_property(c),
+                CharClassification::Space
+                    | CharClassification::Lf
+                    | CharClassification::Cr
+            )
+        };
+        let (start, end) = self.scan_around(initial, is_big_word_char);
+        if start < end {
+            return (start, end);
         }
-        if codepoint == '\n' {
-            return CharClassification::Lf;
+        self.scan_around(initial, |c| {
+            get_char_property(c) == CharClassification::Space
+        })
+    }
+
+    /// Return the range from the cursor to the start of the next word, including
+    /// the whitespace following the current word, as deleted by vim's `dw`. The
+    /// range stops before a line break, so the last word of a line is deleted
+    /// without joining the lines. On whitespace, only the whitespace is included.
+    /// The cursor position is left unchanged.
+    ///
+    /// **Example**:
+    ///
+    ///```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let text = "foo bar";
+    /// let rope = Rope::from(text);
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// let (start, end) = cursor.word_with_trailing_space();
+    /// assert_eq!(&text[start..end], "foo ");
+    ///```
+    pub fn word_with_trailing_space(&mut self) -> (usize, usize) {
+        let start = self.inner.pos();
+        let mut word = self
+            .inner
+            .peek_next_codepoint()
+            .map(|c| self.char_property(c))
+            .filter(|prop| *prop != CharClassification::Space);
+        let mut end = start;
+        while let Some(c) = self.inner.next_codepoint() {
+            let prop = self.char_property(c);
+            if word != Some(prop) {
+                // The word is over, only whitespace may follow
+                word = None;
+                if prop != CharClassification::Space {
+                    break;
+                }
+            }
+            end = self.inner.pos();
         }
-        return CharClassification::Space;
-    } else if codepoint <= '\u{3f}' {
-        if (0xfc00fffe00000000u64 >> (codepoint as u32)) & 1 != 0 {
-            return CharClassification::Punctuation;
+        self.inner.set(start);
+        (start, end)
+    }
+
+    /// Return the boundaries of the run of characters satisfying `pred` around `pos`
+    fn scan_around(
+        &mut self,
+        pos: usize,
+        pred: impl Fn(char) -> bool,
+    ) -> (usize, usize) {
+        self.inner.set(pos);
+        let mut start = pos;
+        while let Some(prev) = self.inner.prev_codepoint() {
+            if !pred(prev) {
+                break;
+            }
+            start = self.inner.pos();
         }
-    } else if codepoint <= '\u{7f}' {
-        // Hardcoded: @[\]^`{|}~
-        if (0x7800000178000001u64 >> ((codepoint as u32) & 0x3f)) & 1 != 0 {
-            return CharClassification::Punctuation;
+        self.inner.set(pos);
+        let mut end = pos;
+        while let Some(next) = self.inner.next_codepoint() {
+            if !pred(next) {
+                break;
+            }
+            end = self.inner.pos();
         }
+        self.inner.set(pos);
+        (start, end)
     }
-    CharClassification::Other
-}
 
-fn classify_boundary(
-    prev: CharClassification,
-    next: CharClassification,
-) -> WordBoundary {
-    use self::{CharClassification::*, WordBoundary::*};
-    match (prev, next) {
-        (Lf, Lf) => Start,
-        (Lf, Space) => Interior,
-        (Cr, Lf) => Interior,
-        (Space, Lf) => Interior,
-        (Space, Cr) => Interior,
-        (Space, Space) => Interior,
-        (_, Space) => End,
-        (Space, _) => Start,
-        (Lf, _) => Start,
-        (_, Cr) => End,
-        (_, Lf) => End,
-        (Punctuation, Other) => Both,
-        (Other, Punctuation) => Both,
-        _ => Interior,
+    /// Return the end and start boundaries of the word under cursor, in this order,
+    /// and set the cursor position to the start of the word.
+    /// This is the leftward counterpart of [`WordCursor::select_word`], the selection
+    /// is anchored at the end of the word and the caret sits at its start.
+    ///
+    /// **Example**:
+    ///
+    ///```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let text = "violet are blue";
+    /// let rope = Rope::from(text);
+    /// let mut cursor = WordCursor::new(&rope, 9);
+    /// let (end, start) = cursor.select_word_reversed();
+    /// assert_eq!(&text[start..end], "are");
+    ///```
+    pub fn select_word_reversed(&mut self) -> (usize, usize) {
+        let initial = self.inner.pos();
+        let start = self.prev_code_boundary();
+        self.inner.set(initial);
+        let end = self.next_code_boundary();
+        self.inner.set(start);
+        (end, start)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use xi_rope::Rope;
+    /// If the word under the cursor is one of the given `(open, close)` keywords,
+    /// return the boundaries of the matching keyword and set the cursor position
+    /// to its start. See [`KeywordBracketCursor`].
+    ///
+    /// **Example:**
+    ///
+    ///```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use xi_rope::Rope;
+    /// let text = "if a then b end";
+    /// let rope = Rope::from(text);
+    /// let mut cursor = WordCursor::new(&rope, 1);
+    /// let position = cursor.match_word_pair(&[("if", "end")]);
+    /// assert_eq!(position, Some((12, 15)));
+    ///```
+    pub fn match_word_pair(
+        &mut self,
+        pairs: &[(&str, &str)],
+    ) -> Option<(usize, usize)> {
+        let text = self.inner.root();
+        let (start, end) = KeywordBracketCursor::new(text, self.inner.pos(), pairs)
+            .match_keyword()?;
+        self.inner.set(start);
+        Some((start, end))
+    }
+}
 
-    use super::WordCursor;
+/// A rope owned along with a cursor position, for tests and callers only having
+/// a `&str`. Cursors borrow their rope, so they are created on demand from the
+/// owned rope, each one starting at the stored position.
+#[derive(Clone)]
+pub struct OwnedCursor {
+    text: Rope,
+    pos: usize,
+}
 
-    #[test]
-    fn prev_boundary_should_be_none_at_position_zero() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 0);
-        let boudary = cursor.prev_boundary();
-        assert!(boudary.is_none())
+impl OwnedCursor {
+    /// Build a rope from `text`, and store it along with the cursor position `pos`.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::OwnedCursor;
+    /// let owned = OwnedCursor::new("Hello world", 0);
+    /// assert_eq!(owned.word_cursor().next_boundary(), Some(6));
+    /// assert_eq!(owned.bracket_cursor().next_bracket(None), None);
+    ///```
+    pub fn new(text: &str, pos: usize) -> Self {
+        Self {
+            text: Rope::from(text),
+            pos,
+        }
     }
 
-    #[test]
-    fn prev_boundary_should_be_zero_when_cursor_on_first_word() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 4);
-        let boundary = cursor.prev_boundary();
-        assert_eq!(boundary, Some(0));
+    /// Get the owned rope, ex: to create other cursors from it
+    pub fn text(&self) -> &Rope {
+        &self.text
     }
 
-    #[test]
-    fn prev_boundary_should_be_at_word_start() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 9);
-        let boundary = cursor.prev_boundary();
-        assert_eq!(boundary, Some(6));
+    /// Create a [`WordCursor`] at the stored position
+    pub fn word_cursor(&self) -> WordCursor<'_> {
+        WordCursor::new(&self.text, self.pos)
     }
 
-    #[test]
-    fn should_get_next_word_boundary() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 0);
-        let boundary = cursor.next_boundary();
-        assert_eq!(boundary, Some(6));
+    /// Create a [`BracketCursor`] at the stored position
+    pub fn bracket_cursor(&self) -> BracketCursor<'_> {
+        BracketCursor::new(&self.text, self.pos)
     }
+}
 
-    #[test]
-    fn next_word_boundary_should_be_none_at_last_position() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 11);
-        let boundary = cursor.next_boundary();
-        assert_eq!(boundary, None);
+/// A cursor providing utility functions to navigate the rope by brackets.
+pub struct BracketCursor<'a> {
+    pub(crate) inner: Cursor<'a, RopeInfo>,
+    skip_quotes: bool,
+    virtual_ranges: &'a [Range<usize>],
+    raw_string_ranges: &'a [Range<usize>],
+    pairs: &'a [(char, char)],
+}
+
+impl<'a> BracketCursor<'a> {
+    /// Create a cursor at `pos`, snapped to a char boundary like
+    /// [`WordCursor::new`].
+    pub fn new(text: &'a Rope, pos: usize) -> BracketCursor<'a> {
+        let inner = Cursor::new(text, snap_to_char_boundary(text, pos));
+        BracketCursor {
+            inner,
+            skip_quotes: false,
+            virtual_ranges: &[],
+            raw_string_ranges: &[],
+            pairs: &[],
+        }
     }
 
-    #[test]
-    fn should_get_previous_code_boundary() {
-        let text = "violet, are\n blue";
-        let rope = Rope::from(text);
-        let mut cursor = WordCursor::new(&rope, 11);
-        let position = cursor.prev_code_boundary();
-        assert_eq!(&text[position..], "are\n blue");
+    /// Create a cursor on a rope containing virtual text (ex: inlay hints) at
+    /// `virtual_ranges`, which must be sorted and not overlapping. Virtual text is
+    /// invisible to the cursor: its brackets are ignored, and both `pos` and the
+    /// offsets returned by the cursor are offsets in the real buffer, without the
+    /// virtual text. A real offset at which virtual text is inserted is placed
+    /// after the virtual text.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// # use xi_rope::Rope;
+    /// // The real buffer is `f(a)`, with the `x: ` hint before `a`
+    /// let rope = Rope::from("f(x: a)");
+    /// let virtual_ranges = [2..5];
+    /// let mut cursor = BracketCursor::new_mapped(&rope, 1, &virtual_ranges);
+    /// assert_eq!(cursor.match_bracket(), Some(3));
+    ///```
+    pub fn new_mapped(
+        text: &'a Rope,
+        pos: usize,
+        virtual_ranges: &'a [Range<usize>],
+    ) -> BracketCursor<'a> {
+        let mut cursor = Self::new(text, 0);
+        cursor.virtual_ranges = virtual_ranges;
+        let pos = cursor.to_virtual(pos);
+        cursor.inner.set(snap_to_char_boundary(text, pos));
+        cursor
     }
 
-    #[test]
-    fn should_get_next_code_boundary() {
-        let text = "violet, are\n blue";
-        let rope = Rope::from(text);
-        let mut cursor = WordCursor::new(&rope, 11);
-        let position = cursor.next_code_boundary();
-        assert_eq!(&text[position..], "\n blue");
+    /// Ignore the brackets inside single line quotes (`"..."` and `'...'`), so
+    /// that `{ "}" }` is matched correctly without syntax information.
+    ///
+    /// Quotes are found by scanning each line from its start, skipping characters
+    /// escaped by a backslash. Strings spanning several lines are not recognized,
+    /// and a lone `'` (ex: a Rust lifetime) may pair with a later one on the same
+    /// line.
+    pub fn with_quote_skipping(mut self, skip_quotes: bool) -> Self {
+        self.skip_quotes = skip_quotes;
+        self
     }
 
-    #[test]
+    /// Ignore the brackets inside `ranges`, the raw string literals found by the
+    /// caller (ex: Rust `r#"..."#`, whose delimiters vary). Unlike quotes skipped
+    /// by [`BracketCursor::with_quote_skipping`], raw strings can span several
+    /// lines and contain any quote. Offsets are offsets of the rope, and both
+    /// options can be combined.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from(r##"{ r#"}"# }"##);
+    /// let raw_strings = [2..8];
+    /// let mut cursor = BracketCursor::new(&rope, 0).with_raw_strings(&raw_strings);
+    /// assert_eq!(cursor.match_bracket(), Some(9));
+    ///```
+    pub fn with_raw_strings(mut self, ranges: &'a [Range<usize>]) -> Self {
+        self.raw_string_ranges = ranges;
+        self
+    }
+
+    /// Match the `(opening, closing)` brackets of `pairs` (ex: `«»` or `⟨⟩`) in
+    /// addition to the default ones. `pairs` is consulted first, so it can also
+    /// override a default bracket. Used by [`BracketCursor::next_bracket`],
+    /// [`BracketCursor::prev_bracket`] and [`BracketCursor::match_bracket`].
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("«a «b» c»");
+    /// let pairs = [('«', '»')];
+    /// let mut cursor = BracketCursor::new(&rope, 0).with_pairs(&pairs);
+    /// assert_eq!(cursor.match_bracket(), Some(11));
+    ///```
+    pub fn with_pairs(mut self, pairs: &'a [(char, char)]) -> Self {
+        self.pairs = pairs;
+        self
+    }
+
+    /// Create a cursor at `pos`, or return `None` if `pos` is past the end of the
+    /// rope or is not on a char boundary.
+    pub fn try_new(text: &'a Rope, pos: usize) -> Option<BracketCursor<'a>> {
+        if !is_char_boundary(text, pos) {
+            return None;
+        }
+        Some(Self::new(text, pos))
+    }
+
+    /// Build a rope from `text` and return it owned along with the cursor position,
+    /// see [`WordCursor::from_str`]. Bracket cursors are then created from it with
+    /// [`OwnedCursor::bracket_cursor`].
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// let owned = BracketCursor::from_str("f(a)", 1);
+    /// assert_eq!(owned.bracket_cursor().match_bracket(), Some(3));
+    ///```
+    pub fn from_str(text: &str, pos: usize) -> OwnedCursor {
+        OwnedCursor::new(text, pos)
+    }
+
+    /// Get the position of the next bracket after the cursor, and set the cursor
+    /// position to it. If `kind` is given, other brackets are ignored.
+    /// Unlike [`WordCursor::next_unmatched`], nesting is not taken into account.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("foo(a) { b }");
+    /// let mut cursor = BracketCursor::new(&rope, 0);
+    /// assert_eq!(cursor.next_bracket(Some('{')), Some(7));
+    ///```
+    pub fn next_bracket(&mut self, kind: Option<char>) -> Option<usize> {
+        let start = self.inner.pos();
+        let mut quotes = LineQuotes::default();
+        self.inner.next_codepoint();
+        while let Some(c) = self.inner.peek_next_codepoint() {
+            let pos = self.inner.pos();
+            if self.is_bracket_of_kind(c, kind) && !self.is_skipped(&mut quotes, pos)
+            {
+                return Some(self.to_real(pos));
+            }
+            self.inner.next_codepoint();
+        }
+        self.inner.set(start);
+        None
+    }
+
+    /// Get the position of the previous bracket before the cursor, and set the
+    /// cursor position to it. If `kind` is given, other brackets are ignored.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{ foo(a) }");
+    /// let mut cursor = BracketCursor::new(&rope, 9);
+    /// assert_eq!(cursor.prev_bracket(Some('{')), Some(0));
+    ///```
+    pub fn prev_bracket(&mut self, kind: Option<char>) -> Option<usize> {
+        let start = self.inner.pos();
+        let mut quotes = LineQuotes::default();
+        while let Some(c) = self.inner.prev_codepoint() {
+            let pos = self.inner.pos();
+            if self.is_bracket_of_kind(c, kind) && !self.is_skipped(&mut quotes, pos)
+            {
+                return Some(self.to_real(pos));
+            }
+        }
+        self.inner.set(start);
+        None
+    }
+
+    /// Get the position of the bracket matching the one under the cursor, and set
+    /// the cursor position to it. Nested pairs are skipped, as well as quoted
+    /// brackets if [`BracketCursor::with_quote_skipping`] is enabled.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("{ \"}\" }");
+    /// let mut cursor = BracketCursor::new(&rope, 0).with_quote_skipping(true);
+    /// assert_eq!(cursor.match_bracket(), Some(6));
+    ///```
+    pub fn match_bracket(&mut self) -> Option<usize> {
+        let start = self.inner.pos();
+        let bracket = self.inner.peek_next_codepoint()?;
+        let (other, forward) = self.matching_bracket(bracket)?;
+        let mut quotes = LineQuotes::default();
+        if self.is_skipped(&mut quotes, start) {
+            return None;
+        }
+
+        let mut depth = 0;
+        if forward {
+            self.inner.next_codepoint();
+        }
+        loop {
+            let (pos, c) = if forward {
+                let pos = self.inner.pos();
+                match self.inner.next_codepoint() {
+                    Some(c) => (pos, c),
+                    None => break,
+                }
+            } else {
+                match self.inner.prev_codepoint() {
+                    Some(c) => (self.inner.pos(), c),
+                    None => break,
+                }
+            };
+            if (c != bracket && c != other) || self.is_skipped(&mut quotes, pos) {
+                continue;
+            }
+            if c == bracket {
+                depth += 1;
+            } else if depth == 0 {
+                self.inner.set(pos);
+                return Some(self.to_real(pos));
+            } else {
+                depth -= 1;
+            }
+        }
+        self.inner.set(start);
+        None
+    }
+
+    /// Return the role of the bracket under the cursor, or `None` if the character
+    /// under the cursor is not a bracket. Custom pairs given to
+    /// [`BracketCursor::with_pairs`] are taken into account.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::{BracketCursor, BracketRole};
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("f(a)");
+    /// let cursor = BracketCursor::new(&rope, 1);
+    /// assert_eq!(cursor.bracket_role(), Some(BracketRole::Open));
+    /// let cursor = BracketCursor::new(&rope, 0);
+    /// assert_eq!(cursor.bracket_role(), None);
+    ///```
+    pub fn bracket_role(&self) -> Option<BracketRole> {
+        let c = Cursor::new(self.inner.root(), self.inner.pos())
+            .peek_next_codepoint()?;
+        let (_, open) = self.matching_bracket(c)?;
+        Some(if open {
+            BracketRole::Open
+        } else {
+            BracketRole::Close
+        })
+    }
+
+    /// Return the bracket matching `c` and whether `c` is an opening bracket,
+    /// looking at the custom pairs before the default ones
+    fn matching_bracket(&self, c: char) -> Option<(char, bool)> {
+        for &(open, close) in self.pairs {
+            if c == open {
+                return Some((close, true));
+            } else if c == close {
+                return Some((open, false));
+            }
+        }
+        Some((matching_char(c)?, matching_pair_direction(c)?))
+    }
+
+    /// Whether `c` is a bracket, and is `kind` if given
+    fn is_bracket_of_kind(&self, c: char, kind: Option<char>) -> bool {
+        match kind {
+            Some(kind) => c == kind,
+            None => self.matching_bracket(c).is_some(),
+        }
+    }
+
+    /// Whether the character at `offset` is virtual, inside a raw string, or
+    /// inside quotes when they are skipped
+    fn is_skipped(&self, quotes: &mut LineQuotes, offset: usize) -> bool {
+        self.virtual_ranges
+            .iter()
+            .chain(self.raw_string_ranges)
+            .any(|range| range.contains(&offset))
+            || (self.skip_quotes && quotes.contains(self.inner.root(), offset))
+    }
+
+    /// Convert an offset of the rope to an offset of the real buffer
+    fn to_real(&self, offset: usize) -> usize {
+        let hidden: usize = self
+            .virtual_ranges
+            .iter()
+            .filter(|range| range.end <= offset)
+            .map(|range| range.len())
+            .sum();
+        offset - hidden
+    }
+
+    /// Convert an offset of the real buffer to an offset of the rope
+    fn to_virtual(&self, offset: usize) -> usize {
+        let mut offset = offset;
+        for range in self.virtual_ranges {
+            if range.start > offset {
+                break;
+            }
+            offset += range.len();
+        }
+        offset
+    }
+
+    /// Return the offset of every bracket of `line` along with the offset of its
+    /// matching bracket, if any, computing them only if they are not in `cache`.
+    /// Brackets are found and matched like [`BracketCursor::match_bracket`], with
+    /// the pairs, skipped ranges and virtual text of this cursor, so a cache must
+    /// only be used with cursors configured the same way. The cursor position is
+    /// left unchanged.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::{BracketCursor, BracketMatchCache};
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("fn a() {\n}");
+    /// let mut cache = BracketMatchCache::new();
+    /// let mut cursor = BracketCursor::new(&rope, 0);
+    /// let matches = cursor.matches_for_line(0, &mut cache);
+    /// assert_eq!(matches, &[(4, Some(5)), (5, Some(4)), (7, Some(9))]);
+    ///```
+    pub fn matches_for_line<'c>(
+        &mut self,
+        line: usize,
+        cache: &'c mut BracketMatchCache,
+    ) -> &'c [(usize, Option<usize>)] {
+        if !cache.contains(line) {
+            let entry = self.line_bracket_matches(line);
+            cache.lines.insert(line, entry);
+        }
+        &cache.lines[&line].matches
+    }
+
+    /// Match every bracket of `line` from the cursor, restoring its position after
+    fn line_bracket_matches(&mut self, line: usize) -> LineBracketMatches {
+        let rope = self.inner.root();
+        let initial = self.inner.pos();
+        let (start, end) = line_bounds(rope, line);
+        let mut quotes = LineQuotes::default();
+        let mut reach = line;
+        let mut matches = Vec::new();
+        for (offset, c) in char_indices(rope, start..end) {
+            if self.matching_bracket(c).is_none()
+                || self.is_skipped(&mut quotes, offset)
+            {
+                continue;
+            }
+            self.inner.set(offset);
+            let other = self.match_bracket();
+            reach = match other {
+                Some(_) => reach.max(rope.line_of_offset(self.inner.pos())),
+                None => usize::MAX,
+            };
+            matches.push((self.to_real(offset), other));
+        }
+        self.inner.set(initial);
+        LineBracketMatches { matches, reach }
+    }
+}
+
+/// Whether `pos` is inside the rope and on a char boundary
+fn is_char_boundary(text: &Rope, pos: usize) -> bool {
+    pos <= text.len() && text.is_codepoint_boundary(pos)
+}
+
+/// Clamp `pos` to the rope, and move it back to the start of the char containing it
+fn snap_to_char_boundary(text: &Rope, pos: usize) -> usize {
+    let pos = pos.min(text.len());
+    text.at_or_prev_codepoint_boundary(pos).unwrap_or(0)
+}
+
+/// The ranges of the single line quotes of the last line looked at, to avoid
+/// scanning a line again for each of its brackets
+#[derive(Default)]
+struct LineQuotes {
+    line: Option<usize>,
+    ranges: Vec<Range<usize>>,
+}
+
+impl LineQuotes {
+    /// Whether `offset` is inside quotes, quotes included
+    fn contains(&mut self, rope: &Rope, offset: usize) -> bool {
+        let line = rope.line_of_offset(offset);
+        if self.line != Some(line) {
+            self.line = Some(line);
+            self.ranges = line_quote_ranges(rope, line);
+        }
+        self.ranges.iter().any(|range| range.contains(&offset))
+    }
+}
+
+/// Return the ranges of the `"` or `'` quotes of `line`, quotes included.
+/// A quote left open at the end of the line is ignored.
+fn line_quote_ranges(rope: &Rope, line: usize) -> Vec<Range<usize>> {
+    let (start, end) = line_bounds(rope, line);
+    let mut ranges = Vec::new();
+    let mut open: Option<(usize, char)> = None;
+    let mut escaped = false;
+    for (offset, c) in char_indices(rope, start..end) {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match open {
+            _ if c == '\\' => escaped = true,
+            None if c == '"' || c == '\'' => open = Some((offset, c)),
+            Some((from, quote)) if c == quote => {
+                ranges.push(from..offset + 1);
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// A resumable scanner yielding the bracket pairs of a range one at a time, so
+/// that computing the pairs of a large document (ex: for folding) can be split
+/// across several frames.
+///
+/// The scanner doesn't borrow the rope: it only stores its progress and its stack
+/// of unmatched opening brackets, and the rope is given to each
+/// [`BracketPairScanner::next_pair`] call. The rope must not be edited between
+/// calls, a new scanner has to be created after an edit.
+#[derive(Clone, Debug)]
+pub struct BracketPairScanner {
+    offset: usize,
+    end: usize,
+    stack: Vec<(usize, char)>,
+}
+
+impl BracketPairScanner {
+    /// Create a scanner of the pairs of `range`
+    pub fn new(range: Range<usize>) -> Self {
+        Self {
+            offset: range.start,
+            end: range.end,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Scan until the next pair is closed and return the offsets of its opening
+    /// and closing brackets. Pairs are returned in the order of their closing
+    /// brackets, so inner pairs come first. A closing bracket not matching the
+    /// last unmatched opening bracket is ignored.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::BracketPairScanner;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("a(b[c])");
+    /// let mut scanner = BracketPairScanner::new(0..rope.len());
+    /// assert_eq!(scanner.next_pair(&rope), Some((3, 5)));
+    /// assert_eq!(scanner.next_pair(&rope), Some((1, 6)));
+    /// assert_eq!(scanner.next_pair(&rope), None);
+    ///```
+    pub fn next_pair(&mut self, rope: &Rope) -> Option<(usize, usize)> {
+        let mut cursor = Cursor::new(rope, self.offset);
+        while cursor.pos() < self.end {
+            let offset = cursor.pos();
+            let c = cursor.next_codepoint()?;
+            self.offset = cursor.pos();
+            match matching_pair_direction(c) {
+                Some(true) => self.stack.push((offset, c)),
+                Some(false) => {
+                    if let Some(&(open, bracket)) = self.stack.last() {
+                        if matching_char(bracket) == Some(c) {
+                            self.stack.pop();
+                            return Some((open, offset));
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        None
+    }
+
+    /// Whether the whole range has been scanned
+    pub fn is_done(&self) -> bool {
+        self.offset >= self.end
+    }
+}
+
+/// A cursor matching keyword delimited blocks, such as `if`/`end` or `begin`/`end`.
+///
+/// Keywords are only recognized as whole words, and nested blocks closed by the
+/// same keyword are skipped.
+pub struct KeywordBracketCursor<'a> {
+    text: &'a Rope,
+    pos: usize,
+    pairs: &'a [(&'a str, &'a str)],
+    case_insensitive: bool,
+}
+
+impl<'a> KeywordBracketCursor<'a> {
+    pub fn new(
+        text: &'a Rope,
+        pos: usize,
+        pairs: &'a [(&'a str, &'a str)],
+    ) -> KeywordBracketCursor<'a> {
+        KeywordBracketCursor {
+            text,
+            pos,
+            pairs,
+            case_insensitive: false,
+        }
+    }
+
+    /// Match keywords regardless of their ASCII case, for languages such as SQL
+    /// where `BEGIN` and `end` form a block.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::KeywordBracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("Begin select 1; END");
+    /// let pairs = [("begin", "end")];
+    /// let mut cursor =
+    ///     KeywordBracketCursor::new(&rope, 0, &pairs).with_case_insensitive(true);
+    /// assert_eq!(cursor.match_keyword(), Some((16, 19)));
+    ///```
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Return the boundaries of the keyword matching the one under the cursor.
+    /// Nested pairs are skipped, and keywords only match whole words, so the `end`
+    /// of `appendix` or `end_time` doesn't close a block.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::KeywordBracketCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("do if a then b end end");
+    /// let pairs = [("do", "end"), ("if", "end")];
+    /// let mut cursor = KeywordBracketCursor::new(&rope, 0, &pairs);
+    /// assert_eq!(cursor.match_keyword(), Some((19, 22)));
+    ///```
+    pub fn match_keyword(&mut self) -> Option<(usize, usize)> {
+        let (start, end) = WordCursor::new(self.text, self.pos).select_code_word();
+        if start == end {
+            return None;
+        }
+        let word = self.text.slice_to_cow(start..end);
+        if let Some(&(_, close)) = self
+            .pairs
+            .iter()
+            .find(|(open, _)| self.keyword_eq(open, &word))
+        {
+            let mut depth = 0;
+            for range in words_forward(self.text, end) {
+                let word = self.text.slice_to_cow(range.clone());
+                if self.keyword_eq(&word, close) {
+                    if depth == 0 {
+                        return Some((range.start, range.end));
+                    }
+                    depth -= 1;
+                } else if self.is_open(&word, close) {
+                    depth += 1;
+                }
+            }
+        } else if self
+            .pairs
+            .iter()
+            .any(|(_, close)| self.keyword_eq(close, &word))
+        {
+            let close = word;
+            let mut depth = 0;
+            for range in words_backward(self.text, start) {
+                let word = self.text.slice_to_cow(range.clone());
+                if self.is_open(&word, &close) {
+                    if depth == 0 {
+                        return Some((range.start, range.end));
+                    }
+                    depth -= 1;
+                } else if self.keyword_eq(&word, &close) {
+                    depth += 1;
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether `word` opens a block closed by `close`
+    fn is_open(&self, word: &str, close: &str) -> bool {
+        self.pairs.iter().any(|(open, other)| {
+            self.keyword_eq(open, word) && self.keyword_eq(other, close)
+        })
+    }
+
+    fn keyword_eq(&self, a: &str, b: &str) -> bool {
+        if self.case_insensitive {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    }
+}
+
+/// Iterate over the words (runs of [`CharClassification::Other`]) after `offset`
+fn words_forward(
+    rope: &Rope,
+    offset: usize,
+) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut cursor = Cursor::new(rope, offset);
+    std::iter::from_fn(move || {
+        let start = loop {
+            let pos = cursor.pos();
+            if get_char_property(cursor.next_codepoint()?)
+                == CharClassification::Other
+            {
+                break pos;
+            }
+        };
+        let mut end = cursor.pos();
+        while let Some(c) = cursor.next_codepoint() {
+            if get_char_property(c) != CharClassification::Other {
+                break;
+            }
+            end = cursor.pos();
+        }
+        cursor.set(end);
+        Some(start..end)
+    })
+}
+
+/// Iterate backward over the words (runs of [`CharClassification::Other`]) before `offset`
+fn words_backward(
+    rope: &Rope,
+    offset: usize,
+) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut cursor = Cursor::new(rope, offset);
+    std::iter::from_fn(move || {
+        let end = loop {
+            let pos = cursor.pos();
+            if get_char_property(cursor.prev_codepoint()?)
+                == CharClassification::Other
+            {
+                break pos;
+            }
+        };
+        let mut start = cursor.pos();
+        while let Some(c) = cursor.prev_codepoint() {
+            if get_char_property(c) != CharClassification::Other {
+                break;
+            }
+            start = cursor.pos();
+        }
+        cursor.set(start);
+        Some(start..end)
+    })
+}
+
+/// A block of a template language, opened and closed by keyword tags
+/// (ex: `{% if %}` and `{% endif %}`), with optional intermediate tags
+/// (ex: `{% elif %}` and `{% else %}`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TemplateBlock<'a> {
+    pub open: &'a str,
+    pub close: &'a str,
+    pub mid: &'a [&'a str],
+}
+
+/// A cursor matching the block tags of template languages, such as Jinja's
+/// `{% for %}`/`{% endfor %}`. Tags are wrapped by the `wrapper` delimiters, and
+/// identified by their first word. Nested blocks are skipped.
+pub struct TemplateBlockCursor<'a> {
+    text: &'a Rope,
+    pos: usize,
+    wrapper: (&'a str, &'a str),
+    blocks: &'a [TemplateBlock<'a>],
+}
+
+impl<'a> TemplateBlockCursor<'a> {
+    pub fn new(
+        text: &'a Rope,
+        pos: usize,
+        wrapper: (&'a str, &'a str),
+        blocks: &'a [TemplateBlock<'a>],
+    ) -> TemplateBlockCursor<'a> {
+        TemplateBlockCursor {
+            text,
+            pos,
+            wrapper,
+            blocks,
+        }
+    }
+
+    /// Return the range of the tag matching the one under the cursor: the closing
+    /// tag of an opening or intermediate tag, and the opening tag of a closing one.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::{TemplateBlock, TemplateBlockCursor};
+    /// # use xi_rope::Rope;
+    /// let text = "{% if a %}b{% else %}c{% endif %}";
+    /// let rope = Rope::from(text);
+    /// let blocks = [TemplateBlock { open: "if", close: "endif", mid: &["else"] }];
+    /// let mut cursor = TemplateBlockCursor::new(&rope, 0, ("{%", "%}"), &blocks);
+    /// let (start, end) = cursor.match_tag().unwrap();
+    /// assert_eq!(&text[start..end], "{% endif %}");
+    ///```
+    pub fn match_tag(&mut self) -> Option<(usize, usize)> {
+        let current = self.tag_at(self.pos)?;
+        let keyword = self.keyword(&current);
+
+        if let Some(block) = self.blocks.iter().find(|b| b.open == keyword) {
+            return self.find_close(current.end, block.close);
+        }
+        let is_mid = |b: &&TemplateBlock| b.mid.iter().any(|mid| *mid == keyword);
+        if let Some(block) = self.blocks.iter().find(is_mid) {
+            return self.find_close(current.end, block.close);
+        }
+        if let Some(block) = self.blocks.iter().find(|b| b.close == keyword) {
+            let mut depth = 0;
+            let mut before = current.start;
+            while let Some(range) = self.prev_tag(before) {
+                let keyword = self.keyword(&range);
+                if self.is_close(&keyword) {
+                    depth += 1;
+                } else if self.is_open(&keyword) {
+                    if depth == 0 {
+                        return (keyword == block.open)
+                            .then_some((range.start, range.end));
+                    }
+                    depth -= 1;
+                }
+                before = range.start;
+            }
+        }
+        None
+    }
+
+    /// Find the tag closing the current block with `close`, after `offset`
+    fn find_close(&self, mut offset: usize, close: &str) -> Option<(usize, usize)> {
+        let mut depth = 0;
+        while let Some(range) = self.next_tag(offset) {
+            let keyword = self.keyword(&range);
+            if self.is_open(&keyword) {
+                depth += 1;
+            } else if self.is_close(&keyword) {
+                if depth == 0 {
+                    return (keyword == close).then_some((range.start, range.end));
+                }
+                depth -= 1;
+            }
+            offset = range.end;
+        }
+        None
+    }
+
+    fn is_open(&self, keyword: &str) -> bool {
+        self.blocks.iter().any(|b| b.open == keyword)
+    }
+
+    fn is_close(&self, keyword: &str) -> bool {
+        self.blocks.iter().any(|b| b.close == keyword)
+    }
+
+    /// Return the range of the tag containing `pos`
+    fn tag_at(&self, pos: usize) -> Option<Range<usize>> {
+        let (open, close) = self.wrapper;
+        let start = self.find_backward(open, pos + open.len())?;
+        let end = self.find_forward(close, start + open.len())? + close.len();
+        (pos < end).then_some(start..end)
+    }
+
+    /// Return the range of the first tag starting at or after `offset`
+    fn next_tag(&self, offset: usize) -> Option<Range<usize>> {
+        let (open, close) = self.wrapper;
+        let start = self.find_forward(open, offset)?;
+        let end = self.find_forward(close, start + open.len())? + close.len();
+        Some(start..end)
+    }
+
+    /// Return the range of the last tag ending at or before `offset`
+    fn prev_tag(&self, offset: usize) -> Option<Range<usize>> {
+        let (open, close) = self.wrapper;
+        let end = self.find_backward(close, offset)?;
+        let start = self.find_backward(open, end)?;
+        Some(start..end + close.len())
+    }
+
+    /// Return the keyword of the tag at `range`, its first word. Whitespace
+    /// control markers (ex: `{%-`) are not part of the keyword.
+    fn keyword(&self, range: &Range<usize>) -> String {
+        let (open, close) = self.wrapper;
+        let inner = self
+            .text
+            .slice_to_cow(range.start + open.len()..range.end - close.len());
+        inner
+            .trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace())
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_string()
+    }
+
+    /// Return the start of the first occurrence of `pattern` at or after `offset`
+    fn find_forward(&self, pattern: &str, offset: usize) -> Option<usize> {
+        let mut cursor =
+            Cursor::new(self.text, snap_to_char_boundary(self.text, offset));
+        loop {
+            let pos = cursor.pos();
+            if pos + pattern.len() > self.text.len() {
+                return None;
+            }
+            if self.matches_at(pos, pattern) {
+                return Some(pos);
+            }
+            cursor.next_codepoint()?;
+        }
+    }
+
+    /// Return the start of the last occurrence of `pattern` ending at or before
+    /// `offset`
+    fn find_backward(&self, pattern: &str, offset: usize) -> Option<usize> {
+        let mut cursor =
+            Cursor::new(self.text, snap_to_char_boundary(self.text, offset));
+        loop {
+            let pos = cursor.pos();
+            if pos + pattern.len() <= offset && self.matches_at(pos, pattern) {
+                return Some(pos);
+            }
+            cursor.prev_codepoint()?;
+        }
+    }
+
+    fn matches_at(&self, pos: usize, pattern: &str) -> bool {
+        pos + pattern.len() <= self.text.len()
+            && pattern
+                .bytes()
+                .enumerate()
+                .all(|(i, b)| self.text.byte_at(pos + i) == b)
+    }
+}
+
+/// A cursor matching delimiters using the same character to open and close
+/// (ex: `|a, b|` in Ruby block arguments). Occurrences are paired by their parity
+/// within a region: the first one opens, the second one closes, and so on.
+/// Occurrences escaped by a backslash are ignored.
+pub struct PairedSameCharCursor<'a> {
+    text: &'a Rope,
+    pos: usize,
+}
+
+impl<'a> PairedSameCharCursor<'a> {
+    pub fn new(text: &'a Rope, pos: usize) -> PairedSameCharCursor<'a> {
+        PairedSameCharCursor { text, pos }
+    }
+
+    /// Return the position of the `c` paired with the one under the cursor,
+    /// counting occurrences in `region` only.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::PairedSameCharCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("list.each { |a, b| a }");
+    /// let mut cursor = PairedSameCharCursor::new(&rope, 12);
+    /// assert_eq!(cursor.match_same_char('|', 0..rope.len()), Some(17));
+    ///```
+    pub fn match_same_char(
+        &mut self,
+        c: char,
+        region: Range<usize>,
+    ) -> Option<usize> {
+        let mut occurrences = Vec::new();
+        let mut backslashes = 0;
+        for (offset, current) in char_indices(self.text, region) {
+            if current == c && backslashes % 2 == 0 {
+                occurrences.push(offset);
+            }
+            if current == '\\' {
+                backslashes += 1;
+            } else {
+                backslashes = 0;
+            }
+        }
+        let index = occurrences.iter().position(|offset| *offset == self.pos)?;
+        if index % 2 == 0 {
+            occurrences.get(index + 1).copied()
+        } else {
+            Some(occurrences[index - 1])
+        }
+    }
+}
+
+/// A cursor matching Markdown emphasis delimiter runs (`*`, `**`, `_`, `__`).
+///
+/// This is a simplified version of the CommonMark rules: a run matches the next
+/// (or previous) run of the same character and the same length, an opening run
+/// must not be followed by whitespace and a closing run must not be preceded by
+/// whitespace. Intraword `_` runs can neither open nor close emphasis.
+pub struct MarkdownDelimCursor<'a> {
+    text: &'a Rope,
+    pos: usize,
+}
+
+impl<'a> MarkdownDelimCursor<'a> {
+    pub fn new(text: &'a Rope, pos: usize) -> MarkdownDelimCursor<'a> {
+        MarkdownDelimCursor { text, pos }
+    }
+
+    /// Return the range of the delimiter run matching the one under the cursor.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::MarkdownDelimCursor;
+    /// # use xi_rope::Rope;
+    /// let rope = Rope::from("some **bold** text");
+    /// let mut cursor = MarkdownDelimCursor::new(&rope, 5);
+    /// assert_eq!(cursor.match_emphasis(), Some(11..13));
+    ///```
+    pub fn match_emphasis(&mut self) -> Option<Range<usize>> {
+        let mut cursor = Cursor::new(self.text, self.pos);
+        let delim = cursor.peek_next_codepoint()?;
+        if delim != '*' && delim != '_' {
+            return None;
+        }
+
+        // Find the boundaries of the run under the cursor
+        let mut start = self.pos;
+        while cursor.prev_codepoint() == Some(delim) {
+            start = cursor.pos();
+        }
+        cursor.set(self.pos);
+        while cursor.peek_next_codepoint() == Some(delim) {
+            cursor.next_codepoint();
+        }
+        let end = cursor.pos();
+        let len = end - start;
+
+        let before = Cursor::new(self.text, start).prev_codepoint();
+        let after = Cursor::new(self.text, end).peek_next_codepoint();
+        if can_open_emphasis(delim, before, after) {
+            if let Some(range) = self.next_closing_run(delim, end, len) {
+                return Some(range);
+            }
+        }
+        if can_close_emphasis(delim, before, after) {
+            return self.prev_opening_run(delim, start, len);
+        }
+        None
+    }
+
+    /// Look forward from `offset` for a closing run of `len` bytes
+    fn next_closing_run(
+        &self,
+        delim: char,
+        offset: usize,
+        len: usize,
+    ) -> Option<Range<usize>> {
+        let mut cursor = Cursor::new(self.text, offset);
+        let mut before = Some(delim);
+        while let Some(c) = cursor.next_codepoint() {
+            if c != delim {
+                before = Some(c);
+                continue;
+            }
+            let run_start = cursor.pos() - delim.len_utf8();
+            while cursor.peek_next_codepoint() == Some(delim) {
+                cursor.next_codepoint();
+            }
+            let run_end = cursor.pos();
+            let after = cursor.peek_next_codepoint();
+            if run_end - run_start == len && can_close_emphasis(delim, before, after)
+            {
+                return Some(run_start..run_end);
+            }
+            before = Some(delim);
+        }
+        None
+    }
+
+    /// Look backward from `offset` for an opening run of `len` bytes
+    fn prev_opening_run(
+        &self,
+        delim: char,
+        offset: usize,
+        len: usize,
+    ) -> Option<Range<usize>> {
+        let mut cursor = Cursor::new(self.text, offset);
+        let mut after = Some(delim);
+        while let Some(c) = cursor.prev_codepoint() {
+            if c != delim {
+                after = Some(c);
+                continue;
+            }
+            let run_end = cursor.pos() + delim.len_utf8();
+            let mut run_start = cursor.pos();
+            let before = loop {
+                match cursor.prev_codepoint() {
+                    Some(c) if c == delim => run_start = cursor.pos(),
+                    other => break other,
+                }
+            };
+            cursor.set(run_start);
+            if run_end - run_start == len && can_open_emphasis(delim, before, after)
+            {
+                return Some(run_start..run_end);
+            }
+            after = Some(delim);
+        }
+        None
+    }
+}
+
+fn can_open_emphasis(
+    delim: char,
+    before: Option<char>,
+    after: Option<char>,
+) -> bool {
+    let followed = after.map(|c| !c.is_whitespace()).unwrap_or(false);
+    let intraword =
+        delim == '_' && before.map(char::is_alphanumeric).unwrap_or(false);
+    followed && !intraword
+}
+
+fn can_close_emphasis(
+    delim: char,
+    before: Option<char>,
+    after: Option<char>,
+) -> bool {
+    let preceded = before.map(|c| !c.is_whitespace()).unwrap_or(false);
+    let intraword =
+        delim == '_' && after.map(char::is_alphanumeric).unwrap_or(false);
+    preceded && !intraword
+}
+
+/// Return the [`CharClassification`] of the input character.
+///
+/// The byte order mark `U+FEFF` (zero width no-break space), found at the start
+/// of some files, is classified as [`CharClassification::Space`] so that it is
+/// not merged into the first word.
+pub fn get_char_property(codepoint: char) -> CharClassification {
+    // Fast path for ascii letters, the most common characters in source code
+    if codepoint.is_ascii_alphabetic() {
+        return CharClassification::Other;
+    }
+    if codepoint <= ' ' {
+        if codepoint == '\r' {
+            return CharClassification::Cr;
+        }
+        if codepoint == '\n' {
+            return CharClassification::Lf;
+        }
+        if codepoint == ' ' || codepoint == '\t' {
+            return CharClassification::Space;
+        }
+        return CharClassification::Control;
+    } else if codepoint <= '\u{3f}' {
+        if (0xfc00fffe00000000u64 >> (codepoint as u32)) & 1 != 0 {
+            return CharClassification::Punctuation;
+        }
+    } else if codepoint <= '\u{7f}' {
+        // Hardcoded: @[\]^`{|}~
+        if (0x7800000178000001u64 >> ((codepoint as u32) & 0x3f)) & 1 != 0 {
+            return CharClassification::Punctuation;
+        }
+        if codepoint == '\u{7f}' {
+            return CharClassification::Control;
+        }
+    } else if codepoint <= '\u{9f}' {
+        return CharClassification::Control;
+    } else if codepoint == '\u{feff}' {
+        return CharClassification::Space;
+    }
+    CharClassification::Other
+}
+
+/// Return the [`CharClassification`] of the input character, taking unicode
+/// properties of non-ascii characters into account.
+///
+/// Unicode space separators (such as the non-breaking space `U+00A0`) are
+/// classified as [`CharClassification::Space`], and the unicode line terminators
+/// (`U+0085`, `U+2028` and `U+2029`) are classified as [`CharClassification::Lf`].
+/// Private use characters, often used for icons, as well as currency (`Sc`) and
+/// math (`Sm`) symbols such as `€` or `×`, are classified as
+/// [`CharClassification::Punctuation`].
+pub fn get_unicode_char_property(codepoint: char) -> CharClassification {
+    match codepoint {
+        '\u{85}' | '\u{2028}' | '\u{2029}' => CharClassification::Lf,
+        '\u{e000}'..='\u{f8ff}' | '\u{f0000}'..='\u{10ffff}' => {
+            CharClassification::Punctuation
+        }
+        c if is_currency_or_math_symbol(c) => CharClassification::Punctuation,
+        '\u{a0}'
+        | '\u{1680}'
+        | '\u{2000}'..='\u{200a}'
+        | '\u{202f}'
+        | '\u{205f}'
+        | '\u{3000}' => CharClassification::Space,
+        _ => get_char_property(codepoint),
+    }
+}
+
+/// Whether `c` is in a hand-picked, partial list of non-ascii currency (`Sc`)
+/// and math (`Sm`) symbols: the common ones of Latin-1, a few scattered
+/// currency signs (ex: `֏`, `؋`, `฿`), the currency symbols, letterlike, arrows
+/// and mathematical operators blocks, and their fullwidth forms. Other symbols
+/// of these categories are not included.
+fn is_currency_or_math_symbol(c: char) -> bool {
+    matches!(
+        c,
+        '\u{a2}'..='\u{a5}'
+            | '\u{ac}'
+            | '\u{b1}'
+            | '\u{d7}'
+            | '\u{f7}'
+            | '\u{58f}'
+            | '\u{60b}'
+            | '\u{e3f}'
+            | '\u{2044}'
+            | '\u{2052}'
+            | '\u{207a}'..='\u{207c}'
+            | '\u{208a}'..='\u{208c}'
+            | '\u{20a0}'..='\u{20c0}'
+            | '\u{2118}'
+            | '\u{2140}'..='\u{2144}'
+            | '\u{214b}'
+            | '\u{2190}'..='\u{2194}'
+            | '\u{21d2}'
+            | '\u{21d4}'
+            | '\u{2200}'..='\u{22ff}'
+            | '\u{27c0}'..='\u{27c4}'
+            | '\u{27c7}'..='\u{27e5}'
+            | '\u{2900}'..='\u{2982}'
+            | '\u{2999}'..='\u{29d7}'
+            | '\u{2a00}'..='\u{2aff}'
+            | '\u{ff04}'
+            | '\u{ff0b}'
+            | '\u{ff1c}'..='\u{ff1e}'
+            | '\u{ffe0}'..='\u{ffe2}'
+            | '\u{ffe5}'..='\u{ffe6}'
+    )
+}
+
+fn classify_boundary(
+    prev: CharClassification,
+    next: CharClassification,
+) -> WordBoundary {
+    use self::{CharClassification::*, WordBoundary::*};
+    match (prev, next) {
+        (Lf, Lf) => Start,
+        (Lf, Space) => Interior,
+        (Cr, Lf) => Interior,
+        (Space, Lf) => Interior,
+        (Space, Cr) => Interior,
+        (Space, Space) => Interior,
+        (_, Space) => End,
+        (Space, _) => Start,
+        (Lf, _) => Start,
+        (_, Cr) => End,
+        (_, Lf) => End,
+        (Punctuation, Other) => Both,
+        (Other, Punctuation) => Both,
+        (Control, Other) | (Control, Punctuation) => Both,
+        (Other, Control) | (Punctuation, Control) => Both,
+        _ => Interior,
+    }
+}
+
+/// Split the identifier found in `range` into its subwords, and return their ranges.
+/// Subwords are separated by `_` or `-`, by a lowercase to uppercase transition,
+/// by the last letter of an uppercase run followed by a lowercase letter
+/// and by transitions between letters and digits.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::split_identifier;
+/// # use xi_rope::Rope;
+/// let text = "getHTTPResponse2";
+/// let rope = Rope::from(text);
+/// let parts: Vec<&str> = split_identifier(&rope, 0..text.len())
+///     .into_iter()
+///     .map(|range| &text[range])
+///     .collect();
+/// assert_eq!(parts, vec!["get", "HTTP", "Response", "2"]);
+///```
+pub fn split_identifier(rope: &Rope, range: Range<usize>) -> Vec<Range<usize>> {
+    let text = rope.slice_to_cow(range.clone());
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut prev: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if is_subword_separator(c) {
+            if let Some(s) = start.take() {
+                parts.push(range.start + s..range.start + i);
+            }
+        } else if let (Some(s), Some(p)) = (start, prev) {
+            let next = chars.peek().map(|(_, c)| *c);
+            if is_subword_start(p, c, next) {
+                parts.push(range.start + s..range.start + i);
+                start = Some(i);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+        prev = Some(c);
+    }
+    if let Some(s) = start {
+        parts.push(range.start + s..range.end);
+    }
+    parts
+}
+
+/// Detect the naming convention of the identifier found in `range`, from its
+/// separators and the case of its subwords (see [`split_identifier`]). Digits
+/// don't have a case and are ignored.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{identifier_style, IdentifierStyle};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("getHttpResponse");
+/// assert_eq!(identifier_style(&rope, 0..rope.len()), IdentifierStyle::CamelCase);
+///```
+pub fn identifier_style(rope: &Rope, range: Range<usize>) -> IdentifierStyle {
+    let text = rope.slice_to_cow(range.clone());
+    let has_lower = text.chars().any(char::is_lowercase);
+    let has_upper = text.chars().any(char::is_uppercase);
+    match (text.contains('_'), text.contains('-')) {
+        (true, true) => IdentifierStyle::Mixed,
+        (true, false) if !has_upper => IdentifierStyle::SnakeCase,
+        (true, false) if !has_lower => IdentifierStyle::ScreamingSnake,
+        (false, true) if !has_upper => IdentifierStyle::KebabCase,
+        (true, false) | (false, true) => IdentifierStyle::Mixed,
+        (false, false) if !has_upper => IdentifierStyle::SnakeCase,
+        (false, false) if !has_lower => IdentifierStyle::ScreamingSnake,
+        (false, false) => {
+            let mut initials =
+                split_identifier(rope, range)
+                    .into_iter()
+                    .filter_map(|part| {
+                        rope.slice_to_cow(part)
+                            .chars()
+                            .next()
+                            .filter(|c| !c.is_numeric())
+                    });
+            let first_upper = initials.next().map_or(false, char::is_uppercase);
+            if !initials.all(char::is_uppercase) {
+                IdentifierStyle::Mixed
+            } else if first_upper {
+                IdentifierStyle::PascalCase
+            } else {
+                IdentifierStyle::CamelCase
+            }
+        }
+    }
+}
+
+/// Rewrite the identifier found in `range` in the `to` naming convention,
+/// keeping its subwords (see [`split_identifier`]). Acronyms are treated as a
+/// single subword, and digits directly following a subword stay attached to it
+/// (ex: `utf8`). Leading and trailing separators (ex: `__init__`) are kept as
+/// they are. Converting to the style of the identifier (see [`identifier_style`])
+/// or to [`IdentifierStyle::Mixed`] returns the identifier unchanged.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{convert_identifier, IdentifierStyle};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("getHTTPResponse");
+/// let style = IdentifierStyle::SnakeCase;
+/// let snake = convert_identifier(&rope, 0..rope.len(), style);
+/// assert_eq!(snake, "get_http_response");
+///```
+pub fn convert_identifier(
+    rope: &Rope,
+    range: Range<usize>,
+    to: IdentifierStyle,
+) -> String {
+    let text = rope.slice_to_cow(range.clone());
+    if to == IdentifierStyle::Mixed || identifier_style(rope, range.clone()) == to {
+        return text.into_owned();
+    }
+    let name = text.trim_start_matches(is_subword_separator);
+    let prefix = &text[..text.len() - name.len()];
+    let name = name.trim_end_matches(is_subword_separator);
+    let suffix = &text[prefix.len() + name.len()..];
+
+    let name_start = range.start + prefix.len();
+    let name_range = name_start..name_start + name.len();
+    let mut words: Vec<String> = Vec::new();
+    let mut prev_end = None;
+    for part in split_identifier(rope, name_range) {
+        let glued = prev_end == Some(part.start);
+        prev_end = Some(part.end);
+        let part = rope.slice_to_cow(part);
+        match words.last_mut() {
+            Some(last) if glued && part.chars().all(|c| c.is_ascii_digit()) => {
+                last.push_str(&part)
+            }
+            _ => words.push(part.to_lowercase()),
+        }
+    }
+
+    let separator = match to {
+        IdentifierStyle::SnakeCase | IdentifierStyle::ScreamingSnake => "_",
+        IdentifierStyle::KebabCase => "-",
+        _ => "",
+    };
+    let mut result = prefix.to_string();
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            // Keep digits apart when there is no separator (ex: `x86_64`)
+            let digits_meet = result.ends_with(|c: char| c.is_ascii_digit())
+                && word.starts_with(|c: char| c.is_ascii_digit());
+            if separator.is_empty() && digits_meet {
+                result.push('_');
+            }
+            result.push_str(separator);
+        }
+        let capitalize = match to {
+            IdentifierStyle::CamelCase => i > 0,
+            IdentifierStyle::PascalCase => true,
+            _ => false,
+        };
+        if to == IdentifierStyle::ScreamingSnake {
+            result.push_str(&word.to_uppercase());
+        } else if capitalize {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                result.extend(first.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+        } else {
+            result.push_str(word);
+        }
+    }
+    result.push_str(suffix);
+    result
+}
+
+/// Find the first word after `pos` whose text satisfies `pred`, and return its
+/// start and end boundaries.
+/// Words are runs of [`CharClassification::Other`] characters, the text given to
+/// `pred` is only allocated when the word spans multiple chunks of the rope.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::next_word_matching;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("hello world Foo bar");
+/// let word = next_word_matching(&rope, 0, |word| word.starts_with('F'));
+/// assert_eq!(word, Some((12, 15)));
+///```
+pub fn next_word_matching(
+    rope: &Rope,
+    pos: usize,
+    pred: impl Fn(&str) -> bool,
+) -> Option<(usize, usize)> {
+    let mut cursor = WordCursor::new(rope, pos);
+    while let Some(start) = cursor.next_boundary() {
+        if start >= rope.len() {
+            break;
+        }
+        let end = WordCursor::new(rope, start).next_code_boundary();
+        if end > start && pred(&rope.slice_to_cow(start..end)) {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+/// Find the bracket pair to highlight around the caret at `pos`, and return the
+/// offsets of the opening and closing brackets.
+/// The character after the caret is checked first, then the one before it.
+/// The matching bracket is searched at most `window` bytes away from the bracket
+/// next to the caret, to keep highlighting responsive in large files.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::highlight_match;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo(bar)");
+/// assert_eq!(highlight_match(&rope, 8, 100), Some((3, 7)));
+/// assert_eq!(highlight_match(&rope, 8, 2), None);
+///```
+pub fn highlight_match(
+    rope: &Rope,
+    pos: usize,
+    window: usize,
+) -> Option<(usize, usize)> {
+    let mut cursor = Cursor::new(rope, pos);
+    let after = cursor.peek_next_codepoint().map(|c| (pos, c));
+    let before = cursor.prev_codepoint().map(|c| (cursor.pos(), c));
+    [after, before]
+        .into_iter()
+        .flatten()
+        .find_map(|(offset, c)| {
+            let other = find_match_within(rope, offset, c, window)?;
+            Some((offset.min(other), offset.max(other)))
+        })
+}
+
+/// Find the bracket matching `c` located at `offset`, at most `window` bytes away
+fn find_match_within(
+    rope: &Rope,
+    offset: usize,
+    c: char,
+    window: usize,
+) -> Option<usize> {
+    let other = matching_char(c)?;
+    let mut depth = 0;
+    if matching_pair_direction(c)? {
+        let limit = offset.saturating_add(window);
+        let mut cursor = Cursor::new(rope, offset + c.len_utf8());
+        while cursor.pos() <= limit {
+            let at = cursor.pos();
+            let current = cursor.next_codepoint()?;
+            if current == other {
+                if depth == 0 {
+                    return Some(at);
+                }
+                depth -= 1;
+            } else if current == c {
+                depth += 1;
+            }
+        }
+    } else {
+        let limit = offset.saturating_sub(window);
+        let mut cursor = Cursor::new(rope, offset);
+        while let Some(current) = cursor.prev_codepoint() {
+            if cursor.pos() < limit {
+                break;
+            }
+            if current == other {
+                if depth == 0 {
+                    return Some(cursor.pos());
+                }
+                depth -= 1;
+            } else if current == c {
+                depth += 1;
+            }
+        }
+    }
+    None
+}
+
+/// Whether deleting `delete_range` would join two separate words into one,
+/// i.e. the characters bordering the deletion are both [`CharClassification::Other`]
+/// and the deleted text contains a word boundary.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::deletion_merges_words;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo bar");
+/// assert!(deletion_merges_words(&rope, 3..4));
+/// assert!(!deletion_merges_words(&rope, 6..7));
+///```
+pub fn deletion_merges_words(rope: &Rope, delete_range: Range<usize>) -> bool {
+    if delete_range.is_empty() {
+        return false;
+    }
+    let is_word_char = |c: Option<char>| {
+        c.map(|c| get_char_property(c) == CharClassification::Other)
+            .unwrap_or(false)
+    };
+    let before = Cursor::new(rope, delete_range.start).prev_codepoint();
+    let after = Cursor::new(rope, delete_range.end).peek_next_codepoint();
+    if !is_word_char(before) || !is_word_char(after) {
+        return false;
+    }
+    rope.slice_to_cow(delete_range)
+        .chars()
+        .any(|c| get_char_property(c) != CharClassification::Other)
+}
+
+/// Find the start of the next hunk in a unified-diff-like buffer, i.e. the first line
+/// of the next run of lines starting with `+` or `-`.
+/// If `pos` is inside a hunk, the rest of this hunk is skipped.
+/// File headers (`+++` and `---`) and any other line (context, `@@` headers)
+/// separate hunks.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::next_hunk;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from(" a\n-b\n+c\n d\n");
+/// assert_eq!(next_hunk(&rope, 0), Some(3));
+/// assert_eq!(next_hunk(&rope, 3), None);
+///```
+pub fn next_hunk(rope: &Rope, pos: usize) -> Option<usize> {
+    let line_start = rope.offset_of_line(rope.line_of_offset(pos));
+    let mut offset = line_start;
+    let mut in_hunk = true;
+    for line in rope.lines_raw(line_start..) {
+        let is_change = is_diff_change_line(&line);
+        if is_change && !in_hunk {
+            return Some(offset);
+        }
+        in_hunk = is_change;
+        offset += line.len();
+    }
+    None
+}
+
+/// Whether a diff line is an addition or a deletion
+fn is_diff_change_line(line: &str) -> bool {
+    (line.starts_with('+') && !line.starts_with("+++"))
+        || (line.starts_with('-') && !line.starts_with("---"))
+}
+
+/// Return the unmatched brackets found in `range`, sorted by offset, in a single pass.
+/// `pairs` lists the `(opening, closing)` brackets to consider. A closing bracket
+/// that doesn't close the innermost open bracket is reported as unmatched.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{unmatched_in, UnmatchedBracket};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("( ] )");
+/// let unmatched = unmatched_in(&rope, 0..rope.len(), &[('(', ')'), ('[', ']')]);
+/// assert_eq!(unmatched, vec![UnmatchedBracket::Closing(2)]);
+///```
+pub fn unmatched_in(
+    rope: &Rope,
+    range: Range<usize>,
+    pairs: &[(char, char)],
+) -> Vec<UnmatchedBracket> {
+    let mut unmatched = Vec::new();
+    let mut stack: Vec<(usize, char)> = Vec::new();
+    for (offset, c) in char_indices(rope, range) {
+        if let Some(&(_, close)) = pairs.iter().find(|(open, _)| *open == c) {
+            stack.push((offset, close));
+        } else if pairs.iter().any(|(_, close)| *close == c) {
+            if stack.last().map(|(_, close)| *close == c).unwrap_or(false) {
+                stack.pop();
+            } else {
+                unmatched.push(UnmatchedBracket::Closing(offset));
+            }
+        }
+    }
+    unmatched.extend(
+        stack
+            .into_iter()
+            .map(|(offset, _)| UnmatchedBracket::Opening(offset)),
+    );
+    unmatched.sort_by_key(|bracket| match bracket {
+        UnmatchedBracket::Opening(offset) | UnmatchedBracket::Closing(offset) => {
+            *offset
+        }
+    });
+    unmatched
+}
+
+/// Count the matched pairs and the unmatched brackets of `range` in a single pass,
+/// for a summary of the bracket health of the visible region (ex: in the minimap).
+/// Brackets are matched the same way as in [`unmatched_in`].
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{bracket_health, BracketHealth};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("f(a[0]))");
+/// let health = bracket_health(&rope, 0..rope.len(), &[('(', ')'), ('[', ']')]);
+/// assert_eq!(
+///     health,
+///     BracketHealth { matched: 2, unmatched_open: 0, unmatched_close: 1 }
+/// );
+///```
+pub fn bracket_health(
+    rope: &Rope,
+    range: Range<usize>,
+    pairs: &[(char, char)],
+) -> BracketHealth {
+    let mut health = BracketHealth::default();
+    let mut stack: Vec<char> = Vec::new();
+    for (_, c) in char_indices(rope, range) {
+        if let Some(&(_, close)) = pairs.iter().find(|(open, _)| *open == c) {
+            stack.push(close);
+        } else if pairs.iter().any(|(_, close)| *close == c) {
+            if stack.last() == Some(&c) {
+                stack.pop();
+                health.matched += 1;
+            } else {
+                health.unmatched_close += 1;
+            }
+        }
+    }
+    health.unmatched_open = stack.len();
+    health
+}
+
+/// Return the offset of the first closing bracket after `start` that doesn't close
+/// the innermost bracket opened since `start`, including a closing bracket with no
+/// open bracket left. `start` should be a checkpoint where the brackets are known
+/// to be balanced, like the start of the last edit, so the scan can stop at the
+/// first error instead of walking the whole document.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::first_mismatch_from;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("fn a() { ) }");
+/// let pairs = [('(', ')'), ('{', '}')];
+/// assert_eq!(first_mismatch_from(&rope, 0, &pairs), Some(9));
+///```
+pub fn first_mismatch_from(
+    rope: &Rope,
+    start: usize,
+    pairs: &[(char, char)],
+) -> Option<usize> {
+    let mut stack: Vec<char> = Vec::new();
+    for (offset, c) in char_indices(rope, start..rope.len()) {
+        if let Some(&(_, close)) = pairs.iter().find(|(open, _)| *open == c) {
+            stack.push(close);
+        } else if pairs.iter().any(|(_, close)| *close == c) {
+            if stack.last() != Some(&c) {
+                return Some(offset);
+            }
+            stack.pop();
+        }
+    }
+    None
+}
+
+/// The brackets of [`matching_pair_direction`], as `(opening, closing)` pairs
+const DEFAULT_PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Find the nearest bracket of `pairs` after `pos`, or before it if not `forward`,
+/// that is not closed between it and `pos`, and return its offset and character.
+/// The brackets in between are tracked on a stack of their expected partners, so
+/// `None` is returned if one of them closes a bracket of another kind, as the
+/// brackets are then malformed.
+fn unmatched_bracket(
+    rope: &Rope,
+    pos: usize,
+    forward: bool,
+    pairs: &[(char, char)],
+) -> Option<(usize, char)> {
+    let mut cursor = Cursor::new(rope, pos);
+    let mut stack = Vec::new();
+    loop {
+        let (offset, c) = if forward {
+            let offset = cursor.pos();
+            (offset, cursor.next_codepoint()?)
+        } else {
+            let c = cursor.prev_codepoint()?;
+            (cursor.pos(), c)
+        };
+        let pair = match pairs.iter().find(|pair| pair.0 == c || pair.1 == c) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        // When scanning backward, closing brackets open a pair and opening brackets
+        // close it
+        let (open, close) = if forward { *pair } else { (pair.1, pair.0) };
+        if c == open {
+            stack.push(close);
+        } else {
+            match stack.pop() {
+                None => return Some((offset, c)),
+                Some(expected) if expected != c => return None,
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+/// Return the interior of the innermost pair of brackets of any kind of `pairs`
+/// enclosing `pos`, for a "select inside brackets" text object, so that the
+/// caller doesn't have to pick the kind of bracket. The interior starts after
+/// the opening bracket and ends before the closing one. Nothing is selected
+/// when the brackets around `pos` don't form a pair, ex: in `[a (b] c)`.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::text_object_inside_any;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("f(a, [b])");
+/// let pairs = [('(', ')'), ('[', ']')];
+/// assert_eq!(text_object_inside_any(&rope, 3, &pairs), Some((2, 8)));
+///```
+pub fn text_object_inside_any(
+    rope: &Rope,
+    pos: usize,
+    pairs: &[(char, char)],
+) -> Option<(usize, usize)> {
+    let (start, open) = unmatched_bracket(rope, pos, false, pairs)?;
+    let (end, close) = unmatched_bracket(rope, pos, true, pairs)?;
+    pairs
+        .contains(&(open, close))
+        .then_some((start + open.len_utf8(), end))
+}
+
+/// Return the range of each comma separated argument inside the parentheses
+/// enclosing `pos`, for signature help and argument navigation. Commas inside
+/// nested brackets or single line quotes (ex: `"a,b"` and `','`) don't split
+/// arguments. Ranges are trimmed of surrounding whitespace and empty arguments
+/// (ex: after a trailing comma) are left out.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::argument_ranges;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo(a, [b, c])");
+/// assert_eq!(argument_ranges(&rope, 5), vec![4..5, 7..13]);
+///```
+pub fn argument_ranges(rope: &Rope, pos: usize) -> Vec<Range<usize>> {
+    let (open, close) = match WordCursor::new(rope, pos).enclosing_pair_of('(') {
+        Some(pair) => pair,
+        None => return Vec::new(),
+    };
+    let mut ranges = Vec::new();
+    let mut push_trimmed = |range: Range<usize>| {
+        let text = rope.slice_to_cow(range.clone());
+        let trimmed = text.trim_start();
+        let start = range.start + text.len() - trimmed.len();
+        let end = start + trimmed.trim_end().len();
+        if start < end {
+            ranges.push(start..end);
+        }
+    };
+
+    let mut depth = 0;
+    let mut start = open + 1;
+    let mut quotes = LineQuotes::default();
+    for (offset, c) in char_indices(rope, open + 1..close) {
+        if quotes.contains(rope, offset) {
+            continue;
+        }
+        match matching_pair_direction(c) {
+            Some(true) => depth += 1,
+            Some(false) => depth -= 1,
+            None if c == ',' && depth == 0 => {
+                push_trimmed(start..offset);
+                start = offset + 1;
+            }
+            None => {}
+        }
+    }
+    push_trimmed(start..close);
+    ranges
+}
+
+/// Return the fold range of every bracket pair of `pairs` spanning multiple lines,
+/// sorted by start offset, in a single pass over the document. Like
+/// [`WordCursor::fold_range`], each range goes from the end of the line of the
+/// opening bracket to the closing bracket. Mismatched closing brackets are ignored.
+/// When several pairs open on the same line, only the outermost one is kept, so
+/// that each line has at most one fold.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::foldable_ranges;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("fn a() {\n    b();\n}");
+/// let ranges = foldable_ranges(&rope, &[('(', ')'), ('{', '}')]);
+/// assert_eq!(ranges, vec![8..18]);
+///```
+pub fn foldable_ranges(rope: &Rope, pairs: &[(char, char)]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut stack: Vec<(usize, char)> = Vec::new();
+    for (offset, c) in char_indices(rope, 0..rope.len()) {
+        if let Some(&(_, close)) = pairs.iter().find(|(open, _)| *open == c) {
+            stack.push((offset, close));
+        } else if stack.last().map(|(_, close)| *close == c).unwrap_or(false) {
+            let (open, _) = stack.pop().unwrap();
+            let line = rope.line_of_offset(open);
+            if rope.line_of_offset(offset) != line {
+                ranges.push(line_bounds(rope, line).1..offset);
+            }
+        }
+    }
+    ranges.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+    ranges.dedup_by_key(|range| range.start);
+    ranges
+}
+
+/// Iterate over the brackets of `range` in a single forward pass, for rainbow
+/// brackets, yielding their offset, character and nesting depth. An opening
+/// bracket reports the depth it opens at and a closing bracket the depth it closes,
+/// so both brackets of a pair have the same depth. Depths start at `0` at the start
+/// of `range`, and become negative after unmatched closing brackets.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::bracket_tokens;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("f(a[0])");
+/// let tokens: Vec<_> = bracket_tokens(&rope, 0..rope.len(), &[('(', ')'), ('[', ']')])
+///     .collect();
+/// assert_eq!(tokens, vec![(1, '(', 0), (3, '[', 1), (5, ']', 1), (6, ')', 0)]);
+///```
+pub fn bracket_tokens<'a>(
+    rope: &'a Rope,
+    range: Range<usize>,
+    pairs: &'a [(char, char)],
+) -> impl Iterator<Item = (usize, char, i32)> + 'a {
+    let mut depth = 0;
+    char_indices(rope, range).filter_map(move |(offset, c)| {
+        if pairs.iter().any(|(open, _)| *open == c) {
+            depth += 1;
+            Some((offset, c, depth - 1))
+        } else if pairs.iter().any(|(_, close)| *close == c) {
+            depth -= 1;
+            Some((offset, c, depth))
+        } else {
+            None
+        }
+    })
+}
+
+/// Iterate over the `(offset, char)` of `range`, without allocating
+fn char_indices(
+    rope: &Rope,
+    range: Range<usize>,
+) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut offset = range.start;
+    rope.iter_chunks(range).flat_map(move |chunk| {
+        let start = offset;
+        offset += chunk.len();
+        chunk.char_indices().map(move |(i, c)| (start + i, c))
+    })
+}
+
+/// Return the start offset and the text of the word prefix ending at `pos`,
+/// to be used for autocompletion. Only the part of the word on the left of the
+/// caret is returned.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{completion_prefix, WordCharSet};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("let value = self.some_field");
+/// let (start, prefix) = completion_prefix(&rope, 22, &WordCharSet::default());
+/// assert_eq!(start, 17);
+/// assert_eq!(prefix, "some_");
+///```
+pub fn completion_prefix<'b>(
+    rope: &'b Rope,
+    pos: usize,
+    word_chars: &WordCharSet,
+) -> (usize, Cow<'b, str>) {
+    let mut cursor = Cursor::new(rope, pos);
+    let mut start = pos;
+    while let Some(prev) = cursor.prev_codepoint() {
+        if !word_chars.contains(prev) {
+            break;
+        }
+        start = cursor.pos();
+    }
+    (start, rope.slice_to_cow(start..pos))
+}
+
+/// Return the range of the leading whitespace (spaces and tabs) of the line
+/// containing `pos`.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::line_indent;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("fn main() {\n    let a = 1;\n}");
+/// assert_eq!(line_indent(&rope, 20), 12..16);
+///```
+pub fn line_indent(rope: &Rope, pos: usize) -> Range<usize> {
+    let start = rope.offset_of_line(rope.line_of_offset(pos));
+    let mut cursor = Cursor::new(rope, start);
+    let mut end = start;
+    while let Some(c) = cursor.next_codepoint() {
+        if c != ' ' && c != '\t' {
+            break;
+        }
+        end = cursor.pos();
+    }
+    start..end
+}
+
+/// Return the visual width of the leading whitespace of the line containing `pos`,
+/// spaces counting for one column and tabs expanding to the next tab stop.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::indent_columns;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("  \tfoo");
+/// assert_eq!(indent_columns(&rope, 0, 4), 4);
+///```
+pub fn indent_columns(rope: &Rope, pos: usize, tab_width: usize) -> usize {
+    column_at(rope, line_indent(rope, pos).end, tab_width)
+}
+
+/// Return the column of the tab stop following `column`, tab stops being every
+/// `tab_width` columns. A `tab_width` of `0` is treated as `1`.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::next_tab_stop;
+/// assert_eq!(next_tab_stop(0, 4), 4);
+/// assert_eq!(next_tab_stop(5, 4), 8);
+/// assert_eq!(next_tab_stop(8, 4), 12);
+///```
+pub fn next_tab_stop(column: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    (column / tab_width + 1) * tab_width
+}
+
+/// Return the visual column of `pos` in its line: tabs expand to the next tab
+/// stop, and wide characters (ex: CJK) take two columns.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::column_at;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("\tlet 字 = 1;");
+/// assert_eq!(column_at(&rope, 5, 4), 8);
+/// assert_eq!(column_at(&rope, 8, 4), 10);
+///```
+pub fn column_at(rope: &Rope, pos: usize, tab_width: usize) -> usize {
+    let start = rope.offset_of_line(rope.line_of_offset(pos));
+    char_indices(rope, start..pos).fold(0, |column, (_, c)| match c {
+        '\t' => next_tab_stop(column, tab_width),
+        c => column + char_width(c),
+    })
+}
+
+/// Return the byte range covered by the visual columns `cols` on each of `lines`,
+/// for column (block) selection. Columns are computed as in [`column_at`]. A
+/// character is selected when it starts inside `cols`, so a tab or a wide
+/// character starting before `cols.start` is left out. Lines too short to reach
+/// `cols` get an empty range at their end.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::column_selection;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("abcdef\nab\n\tcd");
+/// let ranges = column_selection(&rope, 0..3, 2..5, 4);
+/// assert_eq!(ranges, vec![2..5, 9..9, 11..12]);
+///```
+pub fn column_selection(
+    rope: &Rope,
+    lines: Range<usize>,
+    cols: Range<usize>,
+    tab_width: usize,
+) -> Vec<Range<usize>> {
+    let last_line = rope.line_of_offset(rope.len());
+    lines
+        .filter(|&line| line <= last_line)
+        .map(|line| {
+            let (line_start, line_end) = line_bounds(rope, line);
+            let mut start = None;
+            let mut end = None;
+            let mut column = 0;
+            for (offset, c) in char_indices(rope, line_start..line_end) {
+                if start.is_none() && column >= cols.start {
+                    start = Some(offset);
+                }
+                if column >= cols.end {
+                    end = Some(offset);
+                    break;
+                }
+                column = match c {
+                    '\t' => next_tab_stop(column, tab_width),
+                    c => column + char_width(c),
+                };
+            }
+            let start = start.unwrap_or(line_end);
+            start..end.unwrap_or(line_end).max(start)
+        })
+        .collect()
+}
+
+/// Return the offsets where `paragraph` should be broken so that its lines fit
+/// in `width` columns, to reflow it. Each offset is the end of the last word of a
+/// line, the whitespace between it and the next word being replaced by the line
+/// break. Whitespace between words, line breaks included, counts as a single
+/// space, and a word longer than `width` is put alone on its line.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::reflow_breaks;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("aaa bbb ccc");
+/// assert_eq!(reflow_breaks(&rope, 0..rope.len(), 7), vec![7]);
+///```
+pub fn reflow_breaks(
+    rope: &Rope,
+    paragraph: Range<usize>,
+    width: usize,
+) -> Vec<usize> {
+    // End offset and width of each word
+    let mut words = Vec::new();
+    let mut word_width: Option<usize> = None;
+    for (offset, c) in char_indices(rope, paragraph.clone()) {
+        if !c.is_whitespace() {
+            *word_width.get_or_insert(0) += char_width(c);
+        } else if let Some(w) = word_width.take() {
+            words.push((offset, w));
+        }
+    }
+    if let Some(w) = word_width {
+        words.push((paragraph.end, w));
+    }
+
+    let mut breaks = Vec::new();
+    let mut line_width = 0;
+    let mut prev_end = None;
+    for (end, w) in words {
+        match prev_end {
+            Some(prev) if line_width + 1 + w > width => {
+                breaks.push(prev);
+                line_width = w;
+            }
+            Some(_) => line_width += 1 + w,
+            None => line_width = w,
+        }
+        prev_end = Some(end);
+    }
+    breaks
+}
+
+/// Number of columns taken by `c`, `2` for east asian wide and fullwidth
+/// characters and `0` for combining marks. Control characters take one column.
+fn char_width(c: char) -> usize {
+    UnicodeWidthChar::width(c).unwrap_or(1)
+}
+
+/// Return the kind of the line containing `pos`, comments starting with
+/// `line_comment` (ex: `//`). A line with code followed by a comment is
+/// [`LineKind::Code`].
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{line_kind, LineKind};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("a();\n    // b\n");
+/// assert_eq!(line_kind(&rope, 0, "//"), LineKind::Code);
+/// assert_eq!(line_kind(&rope, 5, "//"), LineKind::Comment);
+/// assert_eq!(line_kind(&rope, 14, "//"), LineKind::Blank);
+///```
+pub fn line_kind(rope: &Rope, pos: usize, line_comment: &str) -> LineKind {
+    let line = rope.line_of_offset(pos);
+    let (_, end) = line_bounds(rope, line);
+    let content = line_indent(rope, pos).end;
+    if content >= end {
+        LineKind::Blank
+    } else if !line_comment.is_empty()
+        && rope.slice_to_cow(content..end).starts_with(line_comment)
+    {
+        LineKind::Comment
+    } else {
+        LineKind::Code
+    }
+}
+
+/// Return the boundaries of the logical line containing `pos`: the physical
+/// lines joined by a `continuation` character at their end (ex: `\` in C macros
+/// or shell scripts). The end excludes the line ending. A continuation character
+/// escaped by another one (ex: `\\` at the end of a line) doesn't continue the line.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::logical_line_range;
+/// # use xi_rope::Rope;
+/// let text = "#define A(x) \\\n    (x + 1)\nint a;";
+/// let rope = Rope::from(text);
+/// let (start, end) = logical_line_range(&rope, 20, '\\');
+/// assert_eq!(&text[start..end], "#define A(x) \\\n    (x + 1)");
+///```
+pub fn logical_line_range(
+    rope: &Rope,
+    pos: usize,
+    continuation: char,
+) -> (usize, usize) {
+    let last_line = rope.line_of_offset(rope.len());
+    let is_continued = |line| {
+        let (start, end) = line_bounds(rope, line);
+        let mut cursor = Cursor::new(rope, end);
+        let mut count = 0;
+        while cursor.pos() > start && cursor.prev_codepoint() == Some(continuation) {
+            count += 1;
+        }
+        count % 2 == 1
+    };
+
+    let mut first = rope.line_of_offset(pos);
+    while first > 0 && is_continued(first - 1) {
+        first -= 1;
+    }
+    let mut last = rope.line_of_offset(pos);
+    while last < last_line && is_continued(last) {
+        last += 1;
+    }
+    (line_bounds(rope, first).0, line_bounds(rope, last).1)
+}
+
+/// Return the range of the display row `row` of `logical_line` when the line is
+/// soft wrapped at `wrap_points`, the sorted offsets where each display row
+/// starts, for home and end on wrapped lines. Wrap points outside of the line are
+/// ignored, and the last row ends before the line ending. Return `None` if the
+/// line has no such row.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::display_row_range;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("aaa bbb ccc\nd");
+/// assert_eq!(display_row_range(&rope, 0, &[4, 8], 1), Some(4..8));
+/// assert_eq!(display_row_range(&rope, 0, &[4, 8], 2), Some(8..11));
+///```
+pub fn display_row_range(
+    rope: &Rope,
+    logical_line: usize,
+    wrap_points: &[usize],
+    row: usize,
+) -> Option<Range<usize>> {
+    let (start, end) = line_bounds(rope, logical_line);
+    let mut bounds = std::iter::once(start)
+        .chain(
+            wrap_points
+                .iter()
+                .copied()
+                .filter(|&point| start < point && point < end),
+        )
+        .chain(std::iter::once(end))
+        .skip(row);
+    let row_start = bounds.next()?;
+    let row_end = bounds.next()?;
+    Some(row_start..row_end)
+}
+
+/// Return the range to replace to join the line starting at `line_start` with the
+/// next one, as with vim's `J`, along with its replacement. The range goes from
+/// the end of the content of the first line to the start of the content of the
+/// next one, and is replaced by a single space, or by nothing if either line is
+/// blank. The caret should be placed at the start of the range once replaced.
+/// On the last line, the range is empty.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::join_point;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo(  \n    bar)");
+/// assert_eq!(join_point(&rope, 0), (4..11, " ".to_string()));
+///```
+pub fn join_point(rope: &Rope, line_start: usize) -> (Range<usize>, String) {
+    let line = rope.line_of_offset(line_start);
+    let (start, end) = line_bounds(rope, line);
+    if line >= rope.line_of_offset(rope.len()) {
+        return (end..end, String::new());
+    }
+    let mut cursor = Cursor::new(rope, end);
+    let mut content_end = end;
+    while let Some(c) = cursor.prev_codepoint() {
+        if cursor.pos() < start || !(c == ' ' || c == '\t') {
+            break;
+        }
+        content_end = cursor.pos();
+    }
+    let next_start = rope.offset_of_line(line + 1);
+    let next_content = line_indent(rope, next_start).end;
+    let next_end = line_bounds(rope, line + 1).1;
+    let replacement = if content_end == start || next_content == next_end {
+        ""
+    } else {
+        " "
+    };
+    (content_end..next_content, replacement.to_string())
+}
+
+/// Return the range of the indentation based block (ex: Python, YAML) containing
+/// `pos`: the lines around the current one indented at least as deep as it,
+/// bounded by less indented lines. Blank lines inside the block are included,
+/// the range ends at the end of the last non blank line.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::indent_block_range;
+/// # use xi_rope::Rope;
+/// let text = "if a:\n    b\n    c\nd";
+/// let rope = Rope::from(text);
+/// let (start, end) = indent_block_range(&rope, 10);
+/// assert_eq!(&text[start..end], "    b\n    c");
+///```
+pub fn indent_block_range(rope: &Rope, pos: usize) -> (usize, usize) {
+    let line = rope.line_of_offset(pos);
+    let last_line = rope.line_of_offset(rope.len());
+    let level = match line_indent_level(rope, line) {
+        Some(level) => level,
+        None => return line_bounds(rope, line),
+    };
+    let is_shallower = |line| {
+        line_indent_level(rope, line)
+            .map(|l| l < level)
+            .unwrap_or(false)
+    };
+
+    let mut first = line;
+    while first > 0 && !is_shallower(first - 1) {
+        first -= 1;
+    }
+    while first < line && line_indent_level(rope, first).is_none() {
+        first += 1;
+    }
+    let mut last = line;
+    while last < last_line && !is_shallower(last + 1) {
+        last += 1;
+    }
+    while last > line && line_indent_level(rope, last).is_none() {
+        last -= 1;
+    }
+    (line_bounds(rope, first).0, line_bounds(rope, last).1)
+}
+
+/// Return the start and end offsets of the paragraph containing `pos`: the run
+/// of non blank lines around the current one, bounded by blank lines or the
+/// buffer ends. The range ends at the end of the last line, before its line
+/// ending. On a blank line, only that line is returned.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::select_block_of_lines;
+/// # use xi_rope::Rope;
+/// let text = "a\n\nb\n  c\n\nd";
+/// let rope = Rope::from(text);
+/// let (start, end) = select_block_of_lines(&rope, 4);
+/// assert_eq!(&text[start..end], "b\n  c");
+///```
+pub fn select_block_of_lines(rope: &Rope, pos: usize) -> (usize, usize) {
+    let line = rope.line_of_offset(pos);
+    match paragraph_lines(rope, line) {
+        Some((first, last)) => {
+            (line_bounds(rope, first).0, line_bounds(rope, last).1)
+        }
+        None => line_bounds(rope, line),
+    }
+}
+
+/// Return the start offsets of the blank lines around the paragraph containing
+/// `pos` (see [`select_block_of_lines`]), ex: to reflow the paragraph. The start
+/// or the end of the rope is returned when the paragraph is the first or the last
+/// one. On a blank line, the start of that line is returned twice.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::paragraph_bounds;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("a\n\nb\nc\n\nd");
+/// assert_eq!(paragraph_bounds(&rope, 4), (2, 7));
+/// assert_eq!(paragraph_bounds(&rope, 0), (0, 2));
+///```
+pub fn paragraph_bounds(rope: &Rope, pos: usize) -> (usize, usize) {
+    let line = rope.line_of_offset(pos);
+    let (first, last) = match paragraph_lines(rope, line) {
+        Some(lines) => lines,
+        None => {
+            let start = rope.offset_of_line(line);
+            return (start, start);
+        }
+    };
+    let before = if first > 0 {
+        rope.offset_of_line(first - 1)
+    } else {
+        0
+    };
+    let after = if last < rope.line_of_offset(rope.len()) {
+        rope.offset_of_line(last + 1)
+    } else {
+        rope.len()
+    };
+    (before, after)
+}
+
+/// Return the first and last lines of the run of non blank lines containing
+/// `line`, or `None` if `line` is blank
+fn paragraph_lines(rope: &Rope, line: usize) -> Option<(usize, usize)> {
+    line_indent_level(rope, line)?;
+    let last_line = rope.line_of_offset(rope.len());
+    let mut first = line;
+    while first > 0 && line_indent_level(rope, first - 1).is_some() {
+        first -= 1;
+    }
+    let mut last = line;
+    while last < last_line && line_indent_level(rope, last + 1).is_some() {
+        last += 1;
+    }
+    Some((first, last))
+}
+
+/// Expand the selection `sel` to the next larger region around it, for a single
+/// "expand selection" command. The regions, from the smallest, are:
+///
+/// 1. the subword under the caret (see [`split_identifier`])
+/// 2. the whole word under the caret
+/// 3. the inside of the enclosing brackets, then the brackets included, going
+///    outward through the nested pairs
+/// 4. the lines of the selection
+/// 5. the paragraph (see [`select_block_of_lines`])
+///
+/// The smallest region strictly containing `sel` is returned, so regions equal
+/// to the selection are skipped, and `sel` is returned unchanged when none is
+/// larger.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::expand_once;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("f(a_b)");
+/// assert_eq!(expand_once(&rope, (2, 2)), (2, 3));
+/// assert_eq!(expand_once(&rope, (2, 3)), (2, 5));
+/// assert_eq!(expand_once(&rope, (2, 5)), (1, 6));
+///```
+pub fn expand_once(rope: &Rope, sel: (usize, usize)) -> (usize, usize) {
+    let (start, end) = sel;
+    let mut candidates = Vec::new();
+
+    let (word_start, word_end) = WordCursor::new(rope, start).select_code_word();
+    if word_start < word_end {
+        candidates.extend(
+            split_identifier(rope, word_start..word_end)
+                .into_iter()
+                .map(|range| (range.start, range.end)),
+        );
+        candidates.push((word_start, word_end));
+    }
+
+    let mut pos = start;
+    while let Some((open, close)) = WordCursor::new(rope, pos).enclosing_pair() {
+        candidates.push((open + 1, close));
+        candidates.push((open, close + 1));
+        if open < start && end <= close {
+            break;
+        }
+        pos = open;
+    }
+
+    let first_line = rope.line_of_offset(start);
+    let last_line = rope.line_of_offset(end);
+    candidates.push((
+        line_bounds(rope, first_line).0,
+        line_bounds(rope, last_line).1,
+    ));
+    candidates.push(select_block_of_lines(rope, start));
+
+    candidates
+        .into_iter()
+        .filter(|&(s, e)| s <= start && end <= e && (s, e) != sel)
+        .min_by_key(|&(s, e)| e - s)
+        .unwrap_or(sel)
+}
+
+/// The width of a tab used when no tab width is given, the default of the
+/// `editor.tab-width` setting
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Return the range of lines spanned by the indentation guide drawn at column
+/// `level_col` through `line`: the lines around it indented deeper than
+/// `level_col`. Blank lines between such lines extend the guide, but blank lines
+/// at its ends do not. The range is empty if `line` is not part of the guide.
+/// Columns are computed with [`column_at`], tabs being [`DEFAULT_TAB_WIDTH`]
+/// columns wide.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::indent_guide_range;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("fn a() {\n    b();\n\n    c();\n}");
+/// assert_eq!(indent_guide_range(&rope, 1, 0), 1..4);
+///```
+pub fn indent_guide_range(
+    rope: &Rope,
+    line: usize,
+    level_col: usize,
+) -> Range<usize> {
+    let last_line = rope.line_of_offset(rope.len());
+    let indent_col = |line| {
+        let (start, end) = line_bounds(rope, line);
+        if line_indent(rope, start).end >= end {
+            None
+        } else {
+            Some(indent_columns(rope, start, DEFAULT_TAB_WIDTH))
+        }
+    };
+    let is_inside = |line| match indent_col(line) {
+        Some(col) => col > level_col,
+        None => true,
+    };
+    if !is_inside(line) {
+        return line..line;
+    }
+
+    let mut first = line;
+    while first > 0 && is_inside(first - 1) {
+        first -= 1;
+    }
+    while first < line && indent_col(first).is_none() {
+        first += 1;
+    }
+    let mut last = line;
+    while last < last_line && is_inside(last + 1) {
+        last += 1;
+    }
+    while last > line && indent_col(last).is_none() {
+        last -= 1;
+    }
+    if indent_col(first).is_none() || indent_col(last).is_none() {
+        // Blank line outside of the guide
+        return line..line;
+    }
+    first..last + 1
+}
+
+/// Return the start offset of the parent line of the line containing `pos`: the
+/// nearest preceding non blank line indented less than it, ex: for breadcrumbs in
+/// indentation based languages. The indentation of a blank line is its whitespace.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::parent_line;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("def a():\n    if b:\n        c()");
+/// assert_eq!(parent_line(&rope, 30), Some(9));
+/// assert_eq!(parent_line(&rope, 0), None);
+///```
+pub fn parent_line(rope: &Rope, pos: usize) -> Option<usize> {
+    let line = rope.line_of_offset(pos);
+    let level = line_indent(rope, pos).len();
+    (0..line)
+        .rev()
+        .find(|&line| line_indent_level(rope, line).map_or(false, |l| l < level))
+        .map(|line| rope.offset_of_line(line))
+}
+
+/// Return the range of the header of the innermost `{ }` block enclosing `pos`,
+/// ex: the signature of the current function for breadcrumbs. The header is the
+/// text before the `{`, going back over the previous lines up to a blank line or
+/// a line ending with `;`, `{` or `}`, without the surrounding whitespace.
+/// Return `None` if there is no enclosing block, or if it has no header.
+///
+/// This is a heuristic, without syntax information: attributes or comments
+/// directly above the header are part of it.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::enclosing_block_header;
+/// # use xi_rope::Rope;
+/// let text = "fn main() {\n    run();\n}";
+/// let rope = Rope::from(text);
+/// let (start, end) = enclosing_block_header(&rope, 16).unwrap();
+/// assert_eq!(&text[start..end], "fn main()");
+///```
+pub fn enclosing_block_header(rope: &Rope, pos: usize) -> Option<(usize, usize)> {
+    let (open, _) = WordCursor::new(rope, pos).enclosing_pair_of('{')?;
+    let mut line = rope.line_of_offset(open);
+    while line > 0 {
+        let (start, end) = line_bounds(rope, line - 1);
+        let text = rope.slice_to_cow(start..end);
+        let text = text.trim();
+        if text.is_empty() || text.ends_with(&[';', '{', '}'][..]) {
+            break;
+        }
+        line -= 1;
+    }
+
+    let start = line_indent(rope, rope.offset_of_line(line)).end;
+    let text = rope.slice_to_cow(start..open);
+    let end = start + text.trim_end().len();
+    (start < end).then_some((start, end))
+}
+
+/// Return the indentation width, in bytes, a dedent of the line containing `pos`
+/// should snap to: the indentation of its parent line (see [`parent_line`]), or
+/// `None` for a line without parent.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::dedent_target;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("if a:\n    if b:\n        c()");
+/// assert_eq!(dedent_target(&rope, 24), Some(4));
+///```
+pub fn dedent_target(rope: &Rope, pos: usize) -> Option<usize> {
+    parent_line(rope, pos).map(|offset| line_indent(rope, offset).len())
+}
+
+/// Return the indentation of a new line inserted at `pos` by the Enter key: the
+/// indentation of the current line, with one more `indent_unit` if the text
+/// before `pos` ends with an opening bracket of `pairs`, or one less if the
+/// text after `pos` starts with a closing bracket instead. Whitespace around
+/// `pos` on the line is ignored.
+///
+/// Between an empty pair (ex: `{|}`) the indentation is increased, the caller
+/// being expected to move the closing bracket to its own line, indented like the
+/// current line.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::indent_for_new_line;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("    if a {");
+/// let indent = indent_for_new_line(&rope, rope.len(), "    ", &[('{', '}')]);
+/// assert_eq!(indent, "        ");
+///```
+pub fn indent_for_new_line(
+    rope: &Rope,
+    pos: usize,
+    indent_unit: &str,
+    pairs: &[(char, char)],
+) -> String {
+    let line = rope.line_of_offset(pos);
+    let (start, end) = line_bounds(rope, line);
+    let indent = rope.slice_to_cow(line_indent(rope, pos)).into_owned();
+    let pos = pos.min(end);
+    let before = rope.slice_to_cow(start..pos);
+    let after = rope.slice_to_cow(pos..end);
+
+    let last = before.trim_end().chars().next_back();
+    let next = after.trim_start().chars().next();
+    if last.map_or(false, |c| pairs.iter().any(|(open, _)| *open == c)) {
+        indent + indent_unit
+    } else if next.map_or(false, |c| pairs.iter().any(|(_, close)| *close == c)) {
+        match indent.strip_suffix(indent_unit) {
+            Some(dedented) => dedented.to_string(),
+            None => indent,
+        }
+    } else {
+        indent
+    }
+}
+
+/// Iterate over the lines intersecting `range`, yielding the start offset of each
+/// line and the range of its content, excluding leading and trailing whitespace.
+/// The content range of a blank line is empty.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::content_ranges_by_line;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("  foo  \nbar");
+/// let lines: Vec<_> = content_ranges_by_line(&rope, 0..rope.len()).collect();
+/// assert_eq!(lines, vec![(0, 2..5), (8, 8..11)]);
+///```
+pub fn content_ranges_by_line(
+    rope: &Rope,
+    range: Range<usize>,
+) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+    let first = rope.line_of_offset(range.start);
+    let last = rope.line_of_offset(range.end.min(rope.len()));
+    (first..=last).map(move |line| {
+        let (start, end) = line_bounds(rope, line);
+        let content_start = line_indent(rope, start).end.min(end);
+        let mut content_end = end;
+        let mut cursor = Cursor::new(rope, end);
+        while content_end > content_start {
+            match cursor.prev_codepoint() {
+                Some(' ') | Some('\t') => content_end = cursor.pos(),
+                _ => break,
+            }
+        }
+        (start, content_start..content_end)
+    })
+}
+
+/// Return the indentation width in bytes of the line, or `None` for a blank line
+fn line_indent_level(rope: &Rope, line: usize) -> Option<usize> {
+    let (start, end) = line_bounds(rope, line);
+    let indent = line_indent(rope, start);
+    if indent.end >= end {
+        None
+    } else {
+        Some(indent.len())
+    }
+}
+
+/// Return the offsets of the start and the end of the line, excluding the line ending
+fn line_bounds(rope: &Rope, line: usize) -> (usize, usize) {
+    let start = rope.offset_of_line(line);
+    if line >= rope.line_of_offset(rope.len()) {
+        return (start, rope.len());
+    }
+    let mut end = rope.offset_of_line(line + 1);
+    let mut cursor = Cursor::new(rope, end);
+    if cursor.prev_codepoint() == Some('\n') {
+        end = cursor.pos();
+        if cursor.prev_codepoint() == Some('\r') {
+            end = cursor.pos();
+        }
+    }
+    (start, end)
+}
+
+/// Count the occurrences of every word of at least `min_len` characters in the
+/// rope, for buffer based completion. Words are runs of
+/// [`CharClassification::Other`] characters.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::collect_words;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("let foo = foo.bar();");
+/// let words = collect_words(&rope, 3);
+/// assert_eq!(words.get("foo"), Some(&2));
+/// assert_eq!(words.get("bar"), Some(&1));
+///```
+pub fn collect_words(rope: &Rope, min_len: usize) -> HashMap<String, usize> {
+    let mut words = HashMap::new();
+    let mut word = String::new();
+    let mut word_len = 0;
+    let chars = rope.iter_chunks(..).flat_map(str::chars).chain(Some(' '));
+    for c in chars {
+        if get_char_property(c) == CharClassification::Other {
+            word.push(c);
+            word_len += 1;
+            continue;
+        }
+        if word_len >= min_len && !word.is_empty() {
+            if let Some(count) = words.get_mut(&word) {
+                *count += 1;
+            } else {
+                words.insert(std::mem::take(&mut word), 1);
+            }
+        }
+        word.clear();
+        word_len = 0;
+    }
+    words
+}
+
+/// Return the word under the cursor to search for, for "select occurrence" features.
+/// If the cursor is not on an identifier, the run of punctuation characters under
+/// it is returned instead. Return `None` if the cursor is on whitespace.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::word_query;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("let value = 1;");
+/// let query = word_query(&rope, 6).unwrap();
+/// assert_eq!(query.range, 4..9);
+/// assert_eq!(query.text, "value");
+/// assert!(query.is_identifier);
+///```
+pub fn word_query(rope: &Rope, pos: usize) -> Option<WordQuery> {
+    let (start, end) = WordCursor::new(rope, pos).select_code_word();
+    let (range, is_identifier) = if start < end {
+        (start..end, true)
+    } else {
+        (punctuation_run(rope, pos)?, false)
+    };
+    Some(WordQuery {
+        text: rope.slice_to_cow(range.clone()).into_owned(),
+        range,
+        is_identifier,
+    })
+}
+
+/// Return the boundaries of the operator under the caret (ex: `!=` or `=>`): the
+/// maximal run of punctuation characters on either side of `pos`. This is what
+/// [`WordCursor::select_word`] can't select, as punctuation is not part of words.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::select_operator;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("a != b");
+/// assert_eq!(select_operator(&rope, 3), Some((2, 4)));
+/// assert_eq!(select_operator(&rope, 0), None);
+///```
+pub fn select_operator(rope: &Rope, pos: usize) -> Option<(usize, usize)> {
+    punctuation_run(rope, pos).map(|range| (range.start, range.end))
+}
+
+/// Return the maximal run of punctuation characters under the cursor
+fn punctuation_run(rope: &Rope, pos: usize) -> Option<Range<usize>> {
+    let is_punctuation = |c| get_char_property(c) == CharClassification::Punctuation;
+    let mut cursor = Cursor::new(rope, pos);
+    let mut start = pos;
+    while let Some(c) = cursor.prev_codepoint() {
+        if !is_punctuation(c) {
+            break;
+        }
+        start = cursor.pos();
+    }
+    cursor.set(pos);
+    let mut end = pos;
+    while let Some(c) = cursor.next_codepoint() {
+        if !is_punctuation(c) {
+            break;
+        }
+        end = cursor.pos();
+    }
+    if start < end {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+/// Return the boundaries of the whitespace delimited chunk under the caret,
+/// see [`WordCursor::select_big_word`].
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::select_big_word_at;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo.bar() baz");
+/// assert_eq!(select_big_word_at(&rope, 5), (0, 9));
+///```
+pub fn select_big_word_at(rope: &Rope, pos: usize) -> (usize, usize) {
+    WordCursor::new(rope, pos).select_big_word()
+}
+
+/// Whether the caret at `pos` is on the start or the end of a word, ex: to only
+/// accept a completion there. Unlike the boundaries of [`WordCursor`], which look
+/// at the character under the cursor, both the characters before and after the
+/// caret are considered, so the caret right after a word is on a boundary.
+/// The start and the end of the rope count as whitespace.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::at_word_boundary;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo bar");
+/// assert!(at_word_boundary(&rope, 3));
+/// assert!(!at_word_boundary(&rope, 5));
+///```
+pub fn at_word_boundary(rope: &Rope, pos: usize) -> bool {
+    let mut cursor = Cursor::new(rope, pos);
+    let next = cursor
+        .peek_next_codepoint()
+        .map_or(CharClassification::Space, get_char_property);
+    let prev = cursor
+        .prev_codepoint()
+        .map_or(CharClassification::Space, get_char_property);
+    classify_boundary(prev, next).is_boundary()
+}
+
+/// Return the word boundary nearest to `pos`, in bytes, to snap a clicked caret
+/// to a word edge. Boundaries are the ones of [`at_word_boundary`], and the start
+/// and the end of the rope. On a tie the boundary on the left is returned.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::nearest_boundary;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("hello world");
+/// assert_eq!(nearest_boundary(&rope, 2), 0);
+/// assert_eq!(nearest_boundary(&rope, 9), 11);
+///```
+pub fn nearest_boundary(rope: &Rope, pos: usize) -> usize {
+    let pos = snap_to_char_boundary(rope, pos);
+    let mut cursor = Cursor::new(rope, pos);
+    let left = loop {
+        let offset = cursor.pos();
+        if at_word_boundary(rope, offset) || cursor.prev_codepoint().is_none() {
+            break offset;
+        }
+    };
+    cursor.set(pos);
+    let right = loop {
+        let offset = cursor.pos();
+        if at_word_boundary(rope, offset) || cursor.next_codepoint().is_none() {
+            break offset;
+        }
+    };
+    if pos - left <= right - pos {
+        left
+    } else {
+        right
+    }
+}
+
+/// Whether the offsets `a` and `b` are within the same word, a run of
+/// [`CharClassification::Other`] characters, with no boundary between them.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::same_word;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo_bar baz");
+/// assert!(same_word(&rope, 1, 6));
+/// assert!(!same_word(&rope, 1, 9));
+///```
+pub fn same_word(rope: &Rope, a: usize, b: usize) -> bool {
+    let (from, to) = (a.min(b), a.max(b));
+    let (start, end) = WordCursor::new(rope, from).select_code_word();
+    start < end && start <= from && to <= end
+}
+
+/// Return the offset of the bracket matching `typed` if it were inserted at `pos`,
+/// without editing the rope, to highlight it while typing (ex: the `(` closed by a
+/// `)` about to be typed). The offset is in the rope before the insertion.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::would_match_if_typed;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("(foo");
+/// assert_eq!(would_match_if_typed(&rope, 4, ')'), Some(0));
+///```
+pub fn would_match_if_typed(rope: &Rope, pos: usize, typed: char) -> Option<usize> {
+    let other = matching_char(typed)?;
+    let mut cursor = WordCursor::new(rope, pos);
+    if matching_pair_direction(typed)? {
+        let offset = cursor.next_unmatched(other)?;
+        Some(offset - other.len_utf8())
+    } else {
+        cursor.previous_unmatched(other)
+    }
+}
+
+/// Return the range of the empty bracket pair around `pos`, if any of `pairs` has
+/// its opening bracket right before `pos` and its closing bracket right after it.
+/// Used to delete both brackets when backspace is pressed just after an opening
+/// bracket was auto-closed (ex: `(|)`).
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::backspace_deletes_pair;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("f()");
+/// assert_eq!(backspace_deletes_pair(&rope, 2, &[('(', ')')]), Some(1..3));
+///```
+pub fn backspace_deletes_pair(
+    rope: &Rope,
+    pos: usize,
+    pairs: &[(char, char)],
+) -> Option<Range<usize>> {
+    let mut cursor = Cursor::new(rope, pos);
+    let open = cursor.prev_codepoint()?;
+    let start = cursor.pos();
+    cursor.set(pos);
+    let close = cursor.next_codepoint()?;
+    pairs
+        .contains(&(open, close))
+        .then_some(start..cursor.pos())
+}
+
+/// Return the range to delete when deleting forward from `pos` without breaking
+/// the balance of brackets, for paredit-like structural editing:
+///
+/// - before a matched opening bracket, the whole pair is deleted along with its
+///   content (ex: `|(a)` or `|()`), as deleting both brackets keeps the balance
+/// - before a closing bracket, nothing is deleted
+/// - otherwise, the next character is deleted, including an unmatched opening
+///   bracket, whose deletion can't break a pair
+///
+/// `None` is returned when nothing should be deleted, or at the end of the rope.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::structural_delete_forward;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("f(a)");
+/// assert_eq!(structural_delete_forward(&rope, 2), Some(2..3));
+/// assert_eq!(structural_delete_forward(&rope, 1), Some(1..4));
+/// assert_eq!(structural_delete_forward(&rope, 3), None);
+///```
+pub fn structural_delete_forward(rope: &Rope, pos: usize) -> Option<Range<usize>> {
+    let mut cursor = WordCursor::new(rope, pos);
+    let c = cursor.inner.peek_next_codepoint()?;
+    match matching_pair_direction(c) {
+        Some(true) => match cursor.match_pairs() {
+            Some(close) => {
+                let close_len = matching_char(c).map_or(1, char::len_utf8);
+                Some(pos..close + close_len)
+            }
+            None => Some(pos..pos + c.len_utf8()),
+        },
+        Some(false) => None,
+        None => Some(pos..pos + c.len_utf8()),
+    }
+}
+
+/// Return the offsets of the opening and closing brackets of the pair to highlight
+/// for the caret at `pos`, which can touch two brackets. A closing bracket on the
+/// left of the caret is preferred, so that at a `}{` or `)(` junction the block
+/// that just ended is matched. Otherwise the bracket on the right of the caret is
+/// matched, as with [`WordCursor::match_pairs`].
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::match_pairs_at_junction;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("{a}{b}");
+/// assert_eq!(match_pairs_at_junction(&rope, 3), Some((0, 2)));
+/// assert_eq!(match_pairs_at_junction(&rope, 0), Some((0, 2)));
+///```
+pub fn match_pairs_at_junction(rope: &Rope, pos: usize) -> Option<(usize, usize)> {
+    let mut cursor = Cursor::new(rope, pos);
+    if let Some(c) = cursor.prev_codepoint() {
+        if matching_pair_direction(c) == Some(false) {
+            let close = cursor.pos();
+            if let Some(open) = WordCursor::new(rope, close).match_pairs() {
+                return Some((open, close));
+            }
+        }
+    }
+    let other = WordCursor::new(rope, pos).match_pairs()?;
+    Some((pos.min(other), pos.max(other)))
+}
+
+/// Whether `pos` is inside a string literal delimited by one of `quotes`,
+/// guessed from the parity of the quotes found from the start of the line, ex:
+/// to disable auto-pairing inside strings without syntax information. Quotes
+/// escaped by a backslash are skipped, and other quote characters found inside a
+/// string don't count. A caret right after the opening quote or right before the
+/// closing one is inside the string.
+///
+/// Only single line strings are recognized: a string left open on a previous
+/// line (ex: a multiline string or a block comment containing a quote) gives a
+/// wrong result, as does a lone quote such as a Rust lifetime.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::in_string_heuristic;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from(r#"let s = "a(b";"#);
+/// assert!(in_string_heuristic(&rope, 10, &['"']));
+/// assert!(!in_string_heuristic(&rope, 4, &['"']));
+///```
+pub fn in_string_heuristic(rope: &Rope, pos: usize, quotes: &[char]) -> bool {
+    let start = rope.offset_of_line(rope.line_of_offset(pos));
+    let mut open: Option<char> = None;
+    let mut escaped = false;
+    for (_, c) in char_indices(rope, start..pos) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if open == Some(c) {
+            open = None;
+        } else if open.is_none() && quotes.contains(&c) {
+            open = Some(c);
+        }
+    }
+    open.is_some()
+}
+
+/// Return the boundaries of every occurrence in `search_range` of the word at
+/// `pos`, a run of [`CharClassification::Other`] characters, to highlight them.
+/// With `whole_word`, occurrences that are part of a longer word are skipped.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::word_occurrences;
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("foo(foobar, foo)");
+/// let occurrences = word_occurrences(&rope, 0, 0..rope.len(), true);
+/// assert_eq!(occurrences, vec![(0, 3), (12, 15)]);
+///```
+pub fn word_occurrences(
+    rope: &Rope,
+    pos: usize,
+    search_range: Range<usize>,
+    whole_word: bool,
+) -> Vec<(usize, usize)> {
+    let (start, end) = WordCursor::new(rope, pos).select_code_word();
+    if start == end {
+        return Vec::new();
+    }
+    let word = rope.slice_to_cow(start..end);
+    let is_word_char = |c: Option<char>| {
+        c.map_or(false, |c| get_char_property(c) == CharClassification::Other)
+    };
+    let offset = search_range.start;
+    rope.slice_to_cow(search_range)
+        .match_indices(word.as_ref())
+        .map(|(i, _)| (offset + i, offset + i + word.len()))
+        .filter(|&(start, end)| {
+            !whole_word
+                || !(is_word_char(Cursor::new(rope, start).prev_codepoint())
+                    || is_word_char(Cursor::new(rope, end).peek_next_codepoint()))
+        })
+        .collect()
+}
+
+/// Return the offsets of `range` where a `from` character is followed by a `to`
+/// character, ex: the start of every word following a space. Only transitions
+/// between two characters of `range` are reported.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{find_transitions, CharClassification};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("let a = b;");
+/// let starts = find_transitions(
+///     &rope,
+///     0..rope.len(),
+///     CharClassification::Space,
+///     CharClassification::Other,
+/// );
+/// assert_eq!(starts, vec![4, 8]);
+///```
+pub fn find_transitions(
+    rope: &Rope,
+    range: Range<usize>,
+    from: CharClassification,
+    to: CharClassification,
+) -> Vec<usize> {
+    let mut transitions = Vec::new();
+    let mut prev = None;
+    for (offset, c) in char_indices(rope, range) {
+        let prop = get_char_property(c);
+        if prev == Some(from) && prop == to {
+            transitions.push(offset);
+        }
+        prev = Some(prop);
+    }
+    transitions
+}
+
+/// Iterate over the tokens of `range` along with their kind, derived from the
+/// classification of their characters (see [`WordKind`]). This is a cheap
+/// tokenization for simple coloring when no syntax tree is available.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{classified_words, WordKind};
+/// # use xi_rope::Rope;
+/// let rope = Rope::from("a+=1");
+/// let tokens: Vec<_> = classified_words(&rope, 0..rope.len()).collect();
+/// assert_eq!(
+///     tokens,
+///     vec![
+///         (0..1, WordKind::Identifier),
+///         (1..3, WordKind::Operator),
+///         (3..4, WordKind::Number),
+///     ]
+/// );
+///```
+pub fn classified_words(
+    rope: &Rope,
+    range: Range<usize>,
+) -> impl Iterator<Item = (Range<usize>, WordKind)> + '_ {
+    use self::CharClassification::*;
+    let word_kind = |c: char| match get_char_property(c) {
+        Space | Lf | Cr => WordKind::Whitespace,
+        Punctuation if matching_pair_direction(c).is_none() => WordKind::Operator,
+        Other if c.is_ascii_digit() => WordKind::Number,
+        Other => WordKind::Identifier,
+        Punctuation | Control => WordKind::Other,
+    };
+    let is_word = |kind| matches!(kind, WordKind::Identifier | WordKind::Number);
+    let mut cursor = Cursor::new(rope, range.start);
+    std::iter::from_fn(move || {
+        let start = cursor.pos();
+        if start >= range.end {
+            return None;
+        }
+        let c = cursor.next_codepoint()?;
+        let kind = word_kind(c);
+        while cursor.pos() < range.end {
+            let pos = cursor.pos();
+            let next = match cursor.peek_next_codepoint() {
+                Some(next) => next,
+                None => break,
+            };
+            let next_kind = word_kind(next);
+            let continues = match kind {
+                WordKind::Identifier | WordKind::Number if is_word(next_kind) => {
+                    true
+                }
+                WordKind::Number if next == '.' && pos + 1 < range.end => {
+                    Cursor::new(rope, pos + 1)
+                        .peek_next_codepoint()
+                        .map_or(false, |c| c.is_ascii_digit())
+                }
+                WordKind::Operator | WordKind::Whitespace => next_kind == kind,
+                WordKind::Other => {
+                    get_char_property(c) == Control
+                        && get_char_property(next) == Control
+                }
+                _ => false,
+            };
+            if !continues {
+                break;
+            }
+            cursor.next_codepoint();
+        }
+        Some((start..cursor.pos(), kind))
+    })
+}
+
+/// Detect the line ending used by the rope. The scan stops as soon as two kinds
+/// of line endings are found. A rope without any line break is reported as
+/// [`LineEnding::Lf`].
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{detect_line_ending, LineEnding};
+/// # use xi_rope::Rope;
+/// assert_eq!(detect_line_ending(&Rope::from("a\r\nb\r\n")), LineEnding::CrLf);
+/// assert_eq!(detect_line_ending(&Rope::from("a\nb\r\n")), LineEnding::Mixed);
+///```
+pub fn detect_line_ending(rope: &Rope) -> LineEnding {
+    let mut found = None;
+    let mut chars = char_indices(rope, 0..rope.len()).map(|(_, c)| c).peekable();
+    while let Some(c) = chars.next() {
+        let ending = match c {
+            '\n' => LineEnding::Lf,
+            '\r' if chars.peek() == Some(&'\n') => {
+                chars.next();
+                LineEnding::CrLf
+            }
+            '\r' => LineEnding::Cr,
+            _ => continue,
+        };
+        match found {
+            None => found = Some(ending),
+            Some(found) if found != ending => return LineEnding::Mixed,
+            Some(_) => {}
+        }
+    }
+    found.unwrap_or(LineEnding::Lf)
+}
+
+/// Return the maximal range around `pos` made of characters of `spec`, which is
+/// empty if there are none on either side of `pos`. This generalizes
+/// [`WordCursor::select_word`] to language specific tokens.
+///
+/// **Example:**
+///
+/// ```rust
+/// # use lapce_core::word::{token_at, TokenSpec};
+/// # use xi_rope::Rope;
+/// let text = "x = 0x10 + a_1";
+/// let rope = Rope::from(text);
+/// assert_eq!(&text[token_at(&rope, 6, TokenSpec::NUMBER)], "0x10");
+/// assert_eq!(&text[token_at(&rope, 12, TokenSpec::IDENTIFIER)], "a_1");
+///```
+pub fn token_at(rope: &Rope, pos: usize, spec: TokenSpec) -> Range<usize> {
+    let mut cursor = Cursor::new(rope, pos);
+    let mut start = pos;
+    while let Some(c) = cursor.prev_codepoint() {
+        if !spec.contains(c) {
+            break;
+        }
+        start = cursor.pos();
+    }
+    cursor.set(pos);
+    let mut end = pos;
+    while let Some(c) = cursor.next_codepoint() {
+        if !spec.contains(c) {
+            break;
+        }
+        end = cursor.pos();
+    }
+    start..end
+}
+
+/// Characters separating subwords of an identifier (ex: `snake_case`)
+fn is_subword_separator(c: char) -> bool {
+    c == '_' || c == '-'
+}
+
+/// Whether `c`, preceded by `prev` and followed by `next`, starts a new subword
+fn is_subword_start(prev: char, c: char, next: Option<char>) -> bool {
+    if prev.is_lowercase() && c.is_uppercase() {
+        return true;
+    }
+    // Last letter of an acronym starts the next subword (ex: `HTTPResponse`)
+    if prev.is_uppercase()
+        && c.is_uppercase()
+        && next.map(|n| n.is_lowercase()).unwrap_or(false)
+    {
+        return true;
+    }
+    prev.is_ascii_digit() != c.is_ascii_digit()
+        && (prev.is_alphabetic() || c.is_alphabetic())
+}
+
+#[cfg(test)]
+mod test {
+    use xi_rope::Rope;
+
+    use super::{
+        argument_ranges, at_word_boundary, backspace_deletes_pair, bracket_health,
+        bracket_tokens, classified_words, collect_words, column_at,
+        column_selection, completion_prefix, content_ranges_by_line,
+        convert_identifier, dedent_target, deletion_merges_words,
+        detect_line_ending, display_row_range, enclosing_block_header, expand_once,
+        find_transitions, first_mismatch_from, foldable_ranges, get_char_property,
+        get_unicode_char_property, highlight_match, identifier_style,
+        in_string_heuristic, indent_block_range, indent_columns,
+        indent_for_new_line, indent_guide_range, join_point, line_indent, line_kind,
+        logical_line_range, match_pairs_at_junction, nearest_boundary, next_hunk,
+        next_tab_stop, next_word_matching, paragraph_bounds, parent_line,
+        reflow_breaks, same_word, select_big_word_at, select_block_of_lines,
+        select_operator, split_identifier, structural_delete_forward,
+        text_object_inside_any, token_at, unmatched_in, word_occurrences,
+        word_query, would_match_if_typed, BracketCursor, BracketHealth,
+        BracketMatchCache, BracketPairScanner, BracketRole, CharClassification,
+        IdentifierStyle, IndentStyle, KeywordBracketCursor, LineEnding, LineKind,
+        MarkdownDelimCursor, MatchInfo, MatchResult, OwnedCursor,
+        PairedSameCharCursor, TemplateBlock, TemplateBlockCursor, TokenClass,
+        TokenSpec, UnmatchedBracket, WordCharSet, WordCursor, WordKind, WordOptions,
+        WordQuery,
+    };
+
+    #[test]
+    fn prev_boundary_should_be_none_at_position_zero() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 0);
+        let boudary = cursor.prev_boundary();
+        assert!(boudary.is_none())
+    }
+
+    #[test]
+    fn prev_boundary_should_be_zero_when_cursor_on_first_word() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 4);
+        let boundary = cursor.prev_boundary();
+        assert_eq!(boundary, Some(0));
+    }
+
+    #[test]
+    fn prev_boundary_should_be_at_word_start() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 9);
+        let boundary = cursor.prev_boundary();
+        assert_eq!(boundary, Some(6));
+    }
+
+    #[test]
+    fn should_get_next_word_boundary() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 0);
+        let boundary = cursor.next_boundary();
+        assert_eq!(boundary, Some(6));
+    }
+
+    #[test]
+    fn next_word_boundary_should_be_none_at_last_position() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 11);
+        let boundary = cursor.next_boundary();
+        assert_eq!(boundary, None);
+    }
+
+    #[test]
+    fn should_get_previous_code_boundary() {
+        let text = "violet, are\n blue";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 11);
+        let position = cursor.prev_code_boundary();
+        assert_eq!(&text[position..], "are\n blue");
+    }
+
+    #[test]
+    fn should_get_next_code_boundary() {
+        let text = "violet, are\n blue";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 11);
+        let position = cursor.next_code_boundary();
+        assert_eq!(&text[position..], "\n blue");
+    }
+
+    #[test]
     fn get_next_non_blank_char_should_skip_whitespace() {
         let rope = Rope::from("Hello world");
         let mut cursor = WordCursor::new(&rope, 5);
-        let char_position = cursor.next_non_blank_char();
-        assert_eq!(char_position, 6);
+        let char_position = cursor.next_non_blank_char();
+        assert_eq!(char_position, 6);
+    }
+
+    #[test]
+    fn get_next_non_blank_char_should_return_current_position_on_non_blank_char() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 3);
+        let char_position = cursor.next_non_blank_char();
+        assert_eq!(char_position, 3);
+    }
+
+    #[test]
+    fn should_get_end_boundary() {
+        let rope = Rope::from("Hello world");
+        let mut cursor = WordCursor::new(&rope, 3);
+        let end_boundary = cursor.end_boundary();
+        assert_eq!(end_boundary, Some(5));
+    }
+
+    #[test]
+    fn should_get_next_unmatched_char() {
+        let rope = Rope::from("hello { world");
+        let mut cursor = WordCursor::new(&rope, 0);
+        let position = cursor.next_unmatched('{');
+        assert_eq!(position, Some(7));
+    }
+
+    #[test]
+    fn should_get_next_unmatched_char_witch_matched_chars() {
+        let rope = Rope::from("hello {} world }");
+        let mut cursor = WordCursor::new(&rope, 0);
+        let position = cursor.next_unmatched('}');
+        assert_eq!(position, Some(16));
+    }
+
+    #[test]
+    fn should_get_previous_unmatched_char() {
+        let rope = Rope::from("hello { world");
+        let mut cursor = WordCursor::new(&rope, 12);
+        let position = cursor.previous_unmatched('{');
+        assert_eq!(position, Some(6));
+    }
+
+    #[test]
+    fn should_get_previous_unmatched_char_with_inner_matched_chars() {
+        let rope = Rope::from("{hello {} world");
+        let mut cursor = WordCursor::new(&rope, 10);
+        let position = cursor.previous_unmatched('{');
+        assert_eq!(position, Some(0));
+    }
+
+    #[test]
+    fn should_match_pair_forward() {
+        let text = "{ }";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 0);
+        let position = cursor.match_pairs();
+        assert_eq!(position, Some(2));
+    }
+
+    #[test]
+    fn should_match_pair_backward() {
+        let text = "{ }";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 2);
+        let position = cursor.match_pairs();
+        assert_eq!(position, Some(0));
+    }
+
+    #[test]
+    fn match_pair_should_be_none() {
+        let text = "{ }";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 1);
+        let position = cursor.match_pairs();
+        assert_eq!(position, None);
+    }
+
+    #[test]
+    fn select_word_should_return_word_boundaries() {
+        let text = "violet are blue";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 9);
+        let (start, end) = cursor.select_word();
+        assert_eq!(&text[start..end], "are");
+    }
+
+    #[test]
+    fn should_get_deletion_boundary_backward() {
+        let text = "violet are blue";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 9);
+        let position = cursor.prev_deletion_boundary();
+        let position = position;
+
+        assert_eq!(position, Some(7));
+        assert_eq!(&text[..position.unwrap()], "violet ");
+    }
+
+    #[test]
+    fn nbsp_should_be_part_of_word_by_default() {
+        let rope = Rope::from("Hello\u{a0}world");
+        let mut cursor = WordCursor::new(&rope, 0);
+        let boundary = cursor.next_boundary();
+        assert_eq!(boundary, Some(12));
+    }
+
+    #[test]
+    fn nbsp_should_be_a_boundary_with_unicode_option() {
+        let rope = Rope::from("Hello\u{a0}world");
+        let options = WordOptions {
+            unicode: true,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 0, options);
+        assert_eq!(cursor.next_boundary(), Some(7));
+        let mut cursor = WordCursor::with_options(&rope, 12, options);
+        assert_eq!(cursor.prev_boundary(), Some(7));
+    }
+
+    #[test]
+    fn unicode_space_separators_should_be_space() {
+        for c in ['\u{a0}', '\u{2000}', '\u{200a}', '\u{202f}', '\u{3000}'] {
+            assert_eq!(get_unicode_char_property(c), CharClassification::Space);
+        }
+        assert_eq!(get_unicode_char_property('a'), CharClassification::Other);
+    }
+
+    #[test]
+    fn select_word_reversed_should_return_end_then_start() {
+        let text = "violet are blue";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 9);
+        let (start, end) = cursor.select_word();
+        let mut cursor = WordCursor::new(&rope, 9);
+        let reversed = cursor.select_word_reversed();
+        assert_eq!(reversed, (end, start));
+        assert_eq!(reversed, (10, 7));
+        assert_eq!(cursor.inner.pos(), 7);
+    }
+
+    fn identifier_parts(text: &str) -> Vec<&str> {
+        let rope = Rope::from(text);
+        split_identifier(&rope, 0..text.len())
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn should_split_camel_case_identifier() {
+        assert_eq!(
+            identifier_parts("getHTTPResponse2"),
+            vec!["get", "HTTP", "Response", "2"]
+        );
+        assert_eq!(identifier_parts("camelCase"), vec!["camel", "Case"]);
+    }
+
+    #[test]
+    fn should_split_snake_case_identifier() {
+        assert_eq!(identifier_parts("snake_case"), vec!["snake", "case"]);
+        assert_eq!(identifier_parts("__private_2"), vec!["private", "2"]);
+    }
+
+    #[test]
+    fn should_split_screaming_case_identifier() {
+        assert_eq!(
+            identifier_parts("SCREAMING_CASE"),
+            vec!["SCREAMING", "CASE"]
+        );
+        assert_eq!(identifier_parts("HTTP"), vec!["HTTP"]);
+    }
+
+    #[test]
+    fn split_identifier_should_return_rope_offsets() {
+        let rope = Rope::from("let myVar = 1");
+        assert_eq!(split_identifier(&rope, 4..9), vec![4..6, 6..9]);
+    }
+
+    #[test]
+    fn should_find_next_word_starting_with_capital_letter() {
+        let rope = Rope::from("hello world, Foo.bar Baz");
+        let starts_with_capital =
+            |word: &str| word.starts_with(|c: char| c.is_uppercase());
+        let word = next_word_matching(&rope, 0, starts_with_capital);
+        assert_eq!(word, Some((13, 16)));
+        let word = next_word_matching(&rope, 13, starts_with_capital);
+        assert_eq!(word, Some((21, 24)));
+        let word = next_word_matching(&rope, 21, starts_with_capital);
+        assert_eq!(word, None);
+    }
+
+    #[test]
+    fn highlight_match_should_check_both_sides_of_caret() {
+        let rope = Rope::from("(a)(b)");
+        assert_eq!(highlight_match(&rope, 0, 10), Some((0, 2)));
+        assert_eq!(highlight_match(&rope, 1, 10), Some((0, 2)));
+        assert_eq!(highlight_match(&rope, 6, 10), Some((3, 5)));
+        // The bracket after the caret is preferred
+        assert_eq!(highlight_match(&rope, 3, 10), Some((3, 5)));
+    }
+
+    #[test]
+    fn highlight_match_should_respect_window() {
+        let rope = Rope::from("{ [ ] }");
+        assert_eq!(highlight_match(&rope, 0, 6), Some((0, 6)));
+        assert_eq!(highlight_match(&rope, 0, 5), None);
+        assert_eq!(highlight_match(&rope, 7, 6), Some((0, 6)));
+        assert_eq!(highlight_match(&rope, 7, 5), None);
+    }
+
+    #[test]
+    fn highlight_match_should_be_none_without_bracket() {
+        let rope = Rope::from("a b");
+        assert_eq!(highlight_match(&rope, 1, 10), None);
+    }
+
+    #[test]
+    fn deleting_space_between_words_should_merge_them() {
+        let rope = Rope::from("foo bar");
+        assert!(deletion_merges_words(&rope, 3..4));
+        let rope = Rope::from("foo, bar");
+        assert!(deletion_merges_words(&rope, 3..5));
+    }
+
+    #[test]
+    fn deleting_inside_or_at_end_of_word_should_not_merge() {
+        let rope = Rope::from("foo bar");
+        assert!(!deletion_merges_words(&rope, 6..7));
+        assert!(!deletion_merges_words(&rope, 1..2));
+        assert!(!deletion_merges_words(&rope, 0..4));
+        assert!(!deletion_merges_words(&rope, 3..3));
+    }
+
+    #[test]
+    fn should_navigate_over_diff_hunks() {
+        let text =
+            "--- a/file\n+++ b/file\n@@ -1,4 +1,4 @@\n a\n-b\n+c\n d\n-e\n f\n";
+        let rope = Rope::from(text);
+        let first = next_hunk(&rope, 0).unwrap();
+        assert!(text[first..].starts_with("-b"));
+        let second = next_hunk(&rope, first).unwrap();
+        assert!(text[second..].starts_with("-e"));
+        assert_eq!(next_hunk(&rope, second), None);
+    }
+
+    #[test]
+    fn next_hunk_should_split_hunks_on_headers() {
+        let text = "+a\n@@ -5 +5 @@\n+b\n";
+        let rope = Rope::from(text);
+        let next = next_hunk(&rope, 1).unwrap();
+        assert_eq!(&text[next..], "+b\n");
+    }
+
+    #[test]
+    fn unmatched_in_should_report_mismatched_closer() {
+        let rope = Rope::from("( ] )");
+        let pairs = [('(', ')'), ('[', ']')];
+        let unmatched = unmatched_in(&rope, 0..rope.len(), &pairs);
+        assert_eq!(unmatched, vec![UnmatchedBracket::Closing(2)]);
+    }
+
+    #[test]
+    fn unmatched_in_should_report_openers_and_closers() {
+        let rope = Rope::from("} { ( ) [");
+        let pairs = [('(', ')'), ('[', ']'), ('{', '}')];
+        let unmatched = unmatched_in(&rope, 0..rope.len(), &pairs);
+        assert_eq!(
+            unmatched,
+            vec![
+                UnmatchedBracket::Closing(0),
+                UnmatchedBracket::Opening(2),
+                UnmatchedBracket::Opening(8),
+            ]
+        );
+        assert!(unmatched_in(&rope, 4..7, &pairs).is_empty());
+    }
+
+    #[test]
+    fn unicode_line_separator_should_be_a_line_boundary() {
+        let options = WordOptions {
+            unicode: true,
+            ..Default::default()
+        };
+        let rope = Rope::from("foo\u{2028}bar");
+        let mut cursor = WordCursor::with_options(&rope, 0, options);
+        assert_eq!(cursor.next_boundary(), Some(6));
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.next_boundary(), Some(9));
+    }
+
+    #[test]
+    fn blank_unicode_line_should_stop_word_motion() {
+        let options = WordOptions {
+            unicode: true,
+            ..Default::default()
+        };
+        let rope = Rope::from("foo\u{2028}\u{2028}bar");
+        let mut cursor = WordCursor::with_options(&rope, 0, options);
+        assert_eq!(cursor.next_boundary(), Some(6));
+        assert_eq!(cursor.next_boundary(), Some(9));
+        let mut cursor = WordCursor::with_options(&rope, 12, options);
+        assert_eq!(cursor.prev_boundary(), Some(9));
+    }
+
+    #[test]
+    fn unicode_blank_lines_should_separate_paragraphs_like_line_feeds() {
+        let options = WordOptions {
+            unicode: true,
+            ..Default::default()
+        };
+        for c in ['\n', '\u{85}', '\u{2028}', '\u{2029}'] {
+            let text = format!("foo bar{c}{c}baz qux");
+            let rope = Rope::from(&text);
+            let len = c.len_utf8();
+            // Word motion stops on the blank line between the two paragraphs
+            let stops = [0, 4, 7 + len, 7 + 2 * len, 11 + 2 * len, text.len()];
+            let mut cursor = WordCursor::with_options(&rope, 0, options);
+            for stop in &stops[1..] {
+                assert_eq!(cursor.next_boundary(), Some(*stop), "{c:?}");
+            }
+            for stop in stops[..stops.len() - 1].iter().rev() {
+                assert_eq!(cursor.prev_boundary(), Some(*stop), "{c:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn unicode_line_terminators_should_be_lf() {
+        for c in ['\u{85}', '\u{2028}', '\u{2029}'] {
+            assert_eq!(get_unicode_char_property(c), CharClassification::Lf);
+        }
+    }
+
+    #[test]
+    fn completion_prefix_should_stop_at_caret() {
+        let rope = Rope::from("foo.barbaz");
+        let (start, prefix) = completion_prefix(&rope, 7, &WordCharSet::default());
+        assert_eq!(start, 4);
+        assert_eq!(prefix, "bar");
+    }
+
+    #[test]
+    fn completion_prefix_should_be_empty_after_punctuation() {
+        let rope = Rope::from("foo.");
+        let (start, prefix) = completion_prefix(&rope, 4, &WordCharSet::default());
+        assert_eq!(start, 4);
+        assert_eq!(prefix, "");
+    }
+
+    #[test]
+    fn completion_prefix_should_use_extra_word_chars() {
+        let rope = Rope::from("color: var(--main-bg");
+        let word_chars = WordCharSet::new(&['-']);
+        let (start, prefix) = completion_prefix(&rope, 20, &word_chars);
+        assert_eq!(start, 11);
+        assert_eq!(prefix, "--main-bg");
+    }
+
+    #[test]
+    fn line_bounded_select_word_should_not_cross_line_separator() {
+        let text = "foo\u{2028}bar";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 7);
+        assert_eq!(cursor.select_word(), (0, 9));
+        let options = WordOptions {
+            line_bounded: true,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 7, options);
+        let (start, end) = cursor.select_word();
+        assert_eq!(&text[start..end], "bar");
+        let mut cursor = WordCursor::with_options(&rope, 1, options);
+        let (start, end) = cursor.select_word();
+        assert_eq!(&text[start..end], "foo");
+    }
+
+    #[test]
+    fn line_bounded_select_word_at_line_start() {
+        let options = WordOptions {
+            line_bounded: true,
+            ..Default::default()
+        };
+        for c in ['\u{85}', '\u{2028}', '\u{2029}'] {
+            let text = format!("foo{c}bar");
+            let rope = Rope::from(&text);
+            let line_start = 3 + c.len_utf8();
+            let mut cursor = WordCursor::with_options(&rope, line_start, options);
+            let bounded = cursor.select_word();
+            assert_eq!(bounded, (line_start, text.len()));
+            let mut cursor = WordCursor::new(&rope, line_start);
+            if c == '\u{85}' {
+                // Already a control character, so a boundary without the option
+                assert_eq!(cursor.select_word(), bounded);
+            } else {
+                assert_eq!(cursor.select_word(), (0, text.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn should_match_markdown_italic() {
+        let rope = Rope::from("an *italic* word");
+        let mut cursor = MarkdownDelimCursor::new(&rope, 3);
+        assert_eq!(cursor.match_emphasis(), Some(10..11));
+        let mut cursor = MarkdownDelimCursor::new(&rope, 10);
+        assert_eq!(cursor.match_emphasis(), Some(3..4));
+    }
+
+    #[test]
+    fn should_match_markdown_bold() {
+        let rope = Rope::from("**bold** and *it*");
+        let mut cursor = MarkdownDelimCursor::new(&rope, 1);
+        assert_eq!(cursor.match_emphasis(), Some(6..8));
+        let mut cursor = MarkdownDelimCursor::new(&rope, 7);
+        assert_eq!(cursor.match_emphasis(), Some(0..2));
+        let rope = Rope::from("__bold__");
+        let mut cursor = MarkdownDelimCursor::new(&rope, 0);
+        assert_eq!(cursor.match_emphasis(), Some(6..8));
+    }
+
+    #[test]
+    fn markdown_delimiters_should_respect_flanking() {
+        let rope = Rope::from("a * b * c");
+        let mut cursor = MarkdownDelimCursor::new(&rope, 2);
+        assert_eq!(cursor.match_emphasis(), None);
+        let rope = Rope::from("snake_case_name");
+        let mut cursor = MarkdownDelimCursor::new(&rope, 5);
+        assert_eq!(cursor.match_emphasis(), None);
+        let rope = Rope::from("*a **b** c*");
+        let mut cursor = MarkdownDelimCursor::new(&rope, 0);
+        assert_eq!(cursor.match_emphasis(), Some(10..11));
+    }
+
+    /// The classification before the ascii letters fast path was added
+    fn reference_char_property(codepoint: char) -> CharClassification {
+        if codepoint <= ' ' {
+            if codepoint == '\r' {
+                return CharClassification::Cr;
+            }
+            if codepoint == '\n' {
+                return CharClassification::Lf;
+            }
+            return CharClassification::Space;
+        } else if codepoint <= '\u{3f}' {
+            if (0xfc00fffe00000000u64 >> (codepoint as u32)) & 1 != 0 {
+                return CharClassification::Punctuation;
+            }
+        } else if codepoint <= '\u{7f}' {
+            // Hardcoded: @[\]^`{|}~
+            if (0x7800000178000001u64 >> ((codepoint as u32) & 0x3f)) & 1 != 0 {
+                return CharClassification::Punctuation;
+            }
+        }
+        CharClassification::Other
+    }
+
+    #[test]
+    fn char_property_fast_path_should_not_change_classification() {
+        for codepoint in (0..=0x7fu8).map(char::from).chain(['é', '\u{a0}', '中'])
+        {
+            // Control characters got their own classification after the fast path
+            let expected = if codepoint.is_control()
+                && !matches!(codepoint, '\t' | '\n' | '\r')
+            {
+                CharClassification::Control
+            } else {
+                reference_char_property(codepoint)
+            };
+            assert_eq!(
+                get_char_property(codepoint),
+                expected,
+                "classification changed for {codepoint:?}"
+            );
+        }
+    }
+
+    const PYTHON_BLOCKS: &str =
+        "def f():\n    if x:\n        a()\n\n        b()\n    c()\nd()\n";
+
+    #[test]
+    fn indent_block_range_should_include_inner_blank_lines() {
+        let rope = Rope::from(PYTHON_BLOCKS);
+        let pos = PYTHON_BLOCKS.find("a()").unwrap();
+        let (start, end) = indent_block_range(&rope, pos);
+        assert_eq!(&PYTHON_BLOCKS[start..end], "        a()\n\n        b()");
+    }
+
+    #[test]
+    fn indent_block_range_should_be_bounded_by_shallower_lines() {
+        let rope = Rope::from(PYTHON_BLOCKS);
+        let pos = PYTHON_BLOCKS.find("if").unwrap();
+        let (start, end) = indent_block_range(&rope, pos);
+        assert_eq!(
+            &PYTHON_BLOCKS[start..end],
+            "    if x:\n        a()\n\n        b()\n    c()"
+        );
+        let pos = PYTHON_BLOCKS.find("d()").unwrap();
+        let (start, end) = indent_block_range(&rope, pos);
+        assert_eq!(&PYTHON_BLOCKS[start..end], PYTHON_BLOCKS.trim_end());
+    }
+
+    #[test]
+    fn line_indent_should_handle_tabs_and_blank_lines() {
+        let rope = Rope::from("\t  a\n\nb");
+        assert_eq!(line_indent(&rope, 3), 0..3);
+        assert_eq!(line_indent(&rope, 5), 5..5);
+        assert_eq!(line_indent(&rope, 6), 6..6);
+    }
+
+    #[test]
+    fn should_match_if_end_keywords() {
+        let text = "if a\n  if b\n  end\nend";
+        let rope = Rope::from(text);
+        let pairs = [("if", "end")];
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.match_word_pair(&pairs), Some((18, 21)));
+        assert_eq!(cursor.inner.pos(), 18);
+        let mut cursor = WordCursor::new(&rope, 19);
+        assert_eq!(cursor.match_word_pair(&pairs), Some((0, 2)));
+        let mut cursor = WordCursor::new(&rope, 8);
+        assert_eq!(cursor.match_word_pair(&pairs), Some((14, 17)));
+    }
+
+    #[test]
+    fn match_word_pair_should_be_none_on_other_words() {
+        let rope = Rope::from("if a end");
+        let mut cursor = WordCursor::new(&rope, 3);
+        assert_eq!(cursor.match_word_pair(&[("if", "end")]), None);
+        assert_eq!(cursor.inner.pos(), 3);
+    }
+
+    #[test]
+    fn keyword_bracket_cursor_should_handle_shared_close_keyword() {
+        let text = "do\n  if a then b end\nend";
+        let rope = Rope::from(text);
+        let pairs = [("do", "end"), ("if", "end")];
+        let mut cursor = KeywordBracketCursor::new(&rope, 22, &pairs);
+        assert_eq!(cursor.match_keyword(), Some((0, 2)));
+        let mut cursor = KeywordBracketCursor::new(&rope, 5, &pairs);
+        assert_eq!(cursor.match_keyword(), Some((17, 20)));
+    }
+
+    #[test]
+    fn content_ranges_by_line_should_trim_whitespace() {
+        let text = "fn a() {\n    let b = 1;  \n\n  \t\n}";
+        let rope = Rope::from(text);
+        let lines: Vec<_> = content_ranges_by_line(&rope, 0..rope.len()).collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(&text[lines[0].1.clone()], "fn a() {");
+        assert_eq!(lines[1].0, 9);
+        assert_eq!(&text[lines[1].1.clone()], "let b = 1;");
+        assert!(lines[2].1.is_empty());
+        assert!(lines[3].1.is_empty());
+        assert_eq!(&text[lines[4].1.clone()], "}");
+    }
+
+    #[test]
+    fn content_ranges_by_line_should_only_yield_lines_in_range() {
+        let text = "a\n  b\nc\n";
+        let rope = Rope::from(text);
+        let lines: Vec<_> = content_ranges_by_line(&rope, 3..4).collect();
+        assert_eq!(lines, vec![(2, 4..5)]);
+    }
+
+    #[test]
+    fn should_match_same_char_delimiters() {
+        let text = "|a, b| x |c|";
+        let rope = Rope::from(text);
+        let region = 0..rope.len();
+        let mut cursor = PairedSameCharCursor::new(&rope, 0);
+        assert_eq!(cursor.match_same_char('|', region.clone()), Some(5));
+        let mut cursor = PairedSameCharCursor::new(&rope, 5);
+        assert_eq!(cursor.match_same_char('|', region.clone()), Some(0));
+        let mut cursor = PairedSameCharCursor::new(&rope, 9);
+        assert_eq!(cursor.match_same_char('|', region.clone()), Some(11));
+        let mut cursor = PairedSameCharCursor::new(&rope, 1);
+        assert_eq!(cursor.match_same_char('|', region), None);
+    }
+
+    #[test]
+    fn same_char_delimiters_should_skip_escaped() {
+        let text = r"|a \| b|";
+        let rope = Rope::from(text);
+        let mut cursor = PairedSameCharCursor::new(&rope, 0);
+        assert_eq!(cursor.match_same_char('|', 0..rope.len()), Some(7));
+        let mut cursor = PairedSameCharCursor::new(&rope, 4);
+        assert_eq!(cursor.match_same_char('|', 0..rope.len()), None);
+    }
+
+    #[test]
+    fn same_char_delimiters_should_count_in_region_only() {
+        let text = "| |a| |";
+        let rope = Rope::from(text);
+        let mut cursor = PairedSameCharCursor::new(&rope, 2);
+        assert_eq!(cursor.match_same_char('|', 2..5), Some(4));
+    }
+
+    #[test]
+    fn word_motion_should_cross_string_in_one_step() {
+        let text = "print(\"hello, world\", \"x\") end";
+        let rope = Rope::from(text);
+        let string_ranges = [6..20, 22..25];
+        let options = WordOptions {
+            string_ranges: &string_ranges,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 5, options);
+        assert_eq!(cursor.next_boundary(), Some(6));
+        assert_eq!(cursor.next_boundary(), Some(20));
+        assert_eq!(cursor.next_boundary(), Some(22));
+        assert_eq!(cursor.next_boundary(), Some(25));
+        assert_eq!(cursor.prev_boundary(), Some(22));
+        assert_eq!(cursor.prev_boundary(), Some(20));
+        assert_eq!(cursor.prev_boundary(), Some(6));
+    }
+
+    #[test]
+    fn string_followed_by_space_should_be_one_word() {
+        let text = "a \"b c\" d \"e\"";
+        let rope = Rope::from(text);
+        let string_ranges = [2..7, 10..13];
+        let options = WordOptions {
+            string_ranges: &string_ranges,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 2, options);
+        assert_eq!(cursor.next_boundary(), Some(8));
+        let mut cursor = WordCursor::new(&rope, 2);
+        assert_eq!(cursor.next_boundary(), Some(3));
+    }
+
+    #[test]
+    fn collect_words_should_count_repeated_words() {
+        let rope = Rope::from("fn foo(bar: u8) {\n    foo(bar);\n    foo(b)\n}");
+        let words = collect_words(&rope, 2);
+        assert_eq!(words.get("foo"), Some(&3));
+        assert_eq!(words.get("bar"), Some(&2));
+        assert_eq!(words.get("fn"), Some(&1));
+        assert_eq!(words.get("b"), None);
+        assert_eq!(words.get("{"), None);
+        assert_eq!(words.len(), 4);
+    }
+
+    #[test]
+    fn next_bracket_should_skip_other_kinds() {
+        let rope = Rope::from("f(a) { g(b) { } }");
+        let mut cursor = BracketCursor::new(&rope, 0);
+        assert_eq!(cursor.next_bracket(Some('{')), Some(5));
+        assert_eq!(cursor.next_bracket(Some('{')), Some(12));
+        assert_eq!(cursor.next_bracket(Some('{')), None);
+        assert_eq!(cursor.inner.pos(), 12);
+    }
+
+    #[test]
+    fn next_and_prev_bracket_should_find_any_bracket() {
+        let rope = Rope::from("a (b) [c]");
+        let mut cursor = BracketCursor::new(&rope, 0);
+        assert_eq!(cursor.next_bracket(None), Some(2));
+        assert_eq!(cursor.next_bracket(None), Some(4));
+        assert_eq!(cursor.next_bracket(None), Some(6));
+        assert_eq!(cursor.prev_bracket(None), Some(4));
+        assert_eq!(cursor.prev_bracket(Some('(')), Some(2));
+        assert_eq!(cursor.prev_bracket(None), None);
+    }
+
+    #[test]
+    fn word_cursor_on_empty_rope() {
+        let rope = Rope::from("");
+        let cursor = || WordCursor::new(&rope, 0);
+        assert_eq!(cursor().prev_boundary(), None);
+        assert_eq!(cursor().prev_deletion_boundary(), None);
+        assert_eq!(cursor().next_boundary(), None);
+        assert_eq!(cursor().end_boundary(), None);
+        assert_eq!(cursor().next_non_blank_char(), 0);
+        assert_eq!(cursor().prev_code_boundary(), 0);
+        assert_eq!(cursor().next_code_boundary(), 0);
+        assert_eq!(cursor().match_pairs(), None);
+        assert_eq!(cursor().next_unmatched('}'), None);
+        assert_eq!(cursor().previous_unmatched('{'), None);
+        assert_eq!(cursor().select_word(), (0, 0));
+        assert_eq!(cursor().select_word_reversed(), (0, 0));
+        assert_eq!(cursor().match_word_pair(&[("if", "end")]), None);
+    }
+
+    #[test]
+    fn word_cursor_on_single_space_rope() {
+        let rope = Rope::from(" ");
+        let cursor = |pos| WordCursor::new(&rope, pos);
+        assert_eq!(cursor(0).prev_boundary(), None);
+        assert_eq!(cursor(1).prev_boundary(), Some(0));
+        assert_eq!(cursor(0).prev_deletion_boundary(), None);
+        assert_eq!(cursor(1).prev_deletion_boundary(), Some(0));
+        assert_eq!(cursor(0).next_boundary(), Some(1));
+        assert_eq!(cursor(1).next_boundary(), None);
+        assert_eq!(cursor(0).end_boundary(), Some(1));
+        assert_eq!(cursor(1).end_boundary(), None);
+        assert_eq!(cursor(0).next_non_blank_char(), 1);
+        assert_eq!(cursor(1).next_non_blank_char(), 1);
+        assert_eq!(cursor(1).prev_code_boundary(), 1);
+        assert_eq!(cursor(0).next_code_boundary(), 0);
+        assert_eq!(cursor(0).match_pairs(), None);
+        assert_eq!(cursor(0).select_word(), (0, 0));
+        assert_eq!(cursor(1).select_word_reversed(), (1, 1));
+    }
+
+    #[test]
+    fn word_cursor_on_single_letter_rope() {
+        let rope = Rope::from("a");
+        let cursor = |pos| WordCursor::new(&rope, pos);
+        assert_eq!(cursor(0).prev_boundary(), None);
+        assert_eq!(cursor(1).prev_boundary(), Some(0));
+        assert_eq!(cursor(1).prev_deletion_boundary(), Some(0));
+        assert_eq!(cursor(0).next_boundary(), Some(1));
+        assert_eq!(cursor(1).next_boundary(), None);
+        assert_eq!(cursor(0).end_boundary(), Some(1));
+        assert_eq!(cursor(1).end_boundary(), None);
+        assert_eq!(cursor(0).next_non_blank_char(), 0);
+        assert_eq!(cursor(1).prev_code_boundary(), 0);
+        assert_eq!(cursor(0).next_code_boundary(), 1);
+        assert_eq!(cursor(0).match_pairs(), None);
+        assert_eq!(cursor(0).select_word(), (0, 1));
+        assert_eq!(cursor(1).select_word(), (0, 1));
+        assert_eq!(cursor(0).select_word_reversed(), (1, 0));
+    }
+
+    #[test]
+    fn free_functions_on_degenerate_ropes() {
+        for text in ["", " ", "a"] {
+            let rope = Rope::from(text);
+            for pos in 0..=text.len() {
+                assert_eq!(highlight_match(&rope, pos, 10), None);
+                assert_eq!(next_hunk(&rope, pos), None);
+                assert_eq!(next_word_matching(&rope, pos, |_| true), None);
+                assert!(indent_block_range(&rope, pos).1 <= text.len());
+            }
+            assert!(unmatched_in(&rope, 0..text.len(), &[('(', ')')]).is_empty());
+            assert!(!deletion_merges_words(&rope, 0..text.len()));
+            assert!(split_identifier(&rope, 0..text.len()).len() <= 1);
+        }
+    }
+
+    #[test]
+    fn match_pairs_info_for_inline_pair() {
+        let rope = Rope::from("foo(a, b)");
+        let mut cursor = WordCursor::new(&rope, 8);
+        let info = cursor.match_pairs_info();
+        assert_eq!(
+            info,
+            Some(MatchInfo {
+                offset: 3,
+                bracket: ')',
+                lines: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn match_pairs_info_for_multiline_pair() {
+        let rope = Rope::from("fn a() {\n    b();\n}\n");
+        let mut cursor = WordCursor::new(&rope, 7);
+        let info = cursor.match_pairs_info().unwrap();
+        assert_eq!(info.offset, 18);
+        assert_eq!(info.bracket, '{');
+        assert_eq!(info.lines, 3);
+        let mut cursor = WordCursor::new(&rope, 1);
+        assert_eq!(cursor.match_pairs_info(), None);
+    }
+
+    #[test]
+    fn prev_deletion_boundary_should_delete_space_indent_unit() {
+        let rope = Rope::from("fn a() {\n        b();\n}");
+        let options = WordOptions {
+            indent_style: Some(IndentStyle::Spaces(4)),
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 17, options);
+        assert_eq!(cursor.prev_deletion_boundary(), Some(13));
+        assert_eq!(cursor.prev_deletion_boundary(), Some(9));
+        // At the line start, the line break is deleted as usual
+        assert_eq!(cursor.prev_deletion_boundary(), Some(8));
+        // Misaligned indentation is deleted up to the previous indentation stop
+        let mut cursor = WordCursor::with_options(&rope, 15, options);
+        assert_eq!(cursor.prev_deletion_boundary(), Some(13));
+        let mut cursor = WordCursor::new(&rope, 17);
+        assert_eq!(cursor.prev_deletion_boundary(), Some(9));
+    }
+
+    #[test]
+    fn prev_deletion_boundary_should_delete_tab_indent_unit() {
+        let rope = Rope::from("a\n\t\tb");
+        let options = WordOptions {
+            indent_style: Some(IndentStyle::Tabs),
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 4, options);
+        assert_eq!(cursor.prev_deletion_boundary(), Some(3));
+        assert_eq!(cursor.prev_deletion_boundary(), Some(2));
+        // Outside of the indentation, words are deleted as usual
+        let rope = Rope::from("\tfoo bar");
+        let mut cursor = WordCursor::with_options(&rope, 8, options);
+        assert_eq!(cursor.prev_deletion_boundary(), Some(5));
+    }
+
+    #[test]
+    fn word_query_inside_identifier() {
+        let rope = Rope::from("foo_bar.baz()");
+        let query = word_query(&rope, 2);
+        assert_eq!(
+            query,
+            Some(WordQuery {
+                range: 0..7,
+                text: "foo_bar".to_string(),
+                is_identifier: true,
+            })
+        );
+        let query = word_query(&rope, 7).unwrap();
+        assert_eq!(query.text, "foo_bar");
+    }
+
+    #[test]
+    fn word_query_on_punctuation_and_whitespace() {
+        let rope = Rope::from("a += b");
+        let query = word_query(&rope, 2).unwrap();
+        assert_eq!(query.range, 2..4);
+        assert_eq!(query.text, "+=");
+        assert!(!query.is_identifier);
+        let rope = Rope::from("a  b");
+        assert_eq!(word_query(&rope, 2), None);
+    }
+
+    #[test]
+    fn control_characters_should_not_be_part_of_words() {
+        let text = "foo\u{7}bar";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 1);
+        let (start, end) = cursor.select_word();
+        assert_eq!(&text[start..end], "foo");
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.next_boundary(), Some(3));
+        assert_eq!(cursor.next_boundary(), Some(4));
+    }
+
+    #[test]
+    fn should_classify_control_characters() {
+        for c in ['\0', '\u{7}', '\u{c}', '\u{1b}', '\u{7f}', '\u{9f}'] {
+            assert_eq!(get_char_property(c), CharClassification::Control);
+        }
+        for c in [' ', '\t'] {
+            assert_eq!(get_char_property(c), CharClassification::Space);
+        }
+        assert!(
+            get_unicode_char_property('\u{e000}') == CharClassification::Punctuation
+        );
+    }
+
+    #[test]
+    fn enclosing_pair_of_should_ignore_other_brackets() {
+        let rope = Rope::from("{ ( x ) }");
+        let mut cursor = WordCursor::new(&rope, 4);
+        assert_eq!(cursor.enclosing_pair_of('{'), Some((0, 8)));
+        assert_eq!(cursor.enclosing_pair_of('('), Some((2, 6)));
+        assert_eq!(cursor.enclosing_pair_of('['), None);
+        assert_eq!(cursor.inner.pos(), 4);
+    }
+
+    #[test]
+    fn enclosing_pair_of_should_skip_nested_pairs() {
+        let rope = Rope::from("{ {} x {} }");
+        let mut cursor = WordCursor::new(&rope, 5);
+        assert_eq!(cursor.enclosing_pair_of('{'), Some((0, 10)));
+        let mut cursor = WordCursor::new(&rope, 5);
+        assert_eq!(cursor.enclosing_pair_of('}'), None);
+    }
+
+    #[test]
+    fn char_classification_should_have_names() {
+        let names = [
+            (CharClassification::Cr, "Cr"),
+            (CharClassification::Lf, "Lf"),
+            (CharClassification::Space, "Space"),
+            (CharClassification::Punctuation, "Punctuation"),
+            (CharClassification::Control, "Control"),
+            (CharClassification::Other, "Other"),
+        ];
+        for (class, name) in names {
+            assert_eq!(class.as_str(), name);
+            assert_eq!(class.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn next_subword_boundary_should_split_identifiers() {
+        let rope = Rope::from("camelCase snake_case");
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.next_subword_boundary(), Some(5));
+        assert_eq!(cursor.next_subword_boundary(), Some(10));
+        assert_eq!(cursor.next_subword_boundary(), Some(16));
+        assert_eq!(cursor.next_subword_boundary(), Some(20));
+        assert_eq!(cursor.next_subword_boundary(), None);
+    }
+
+    #[test]
+    fn compound_dictionary_words_should_stay_whole() {
+        let rope = Rope::from("isMacOS camelCase PostgreSQLDriver");
+        let dictionary = ["MacOS", "PostgreSQL"];
+        let mut cursor =
+            WordCursor::new(&rope, 0).with_compound_dictionary(&dictionary);
+        assert_eq!(cursor.next_subword_boundary(), Some(2));
+        assert_eq!(cursor.next_subword_boundary(), Some(8));
+        assert_eq!(cursor.next_subword_boundary(), Some(13));
+        assert_eq!(cursor.next_subword_boundary(), Some(18));
+        assert_eq!(cursor.next_subword_boundary(), Some(28));
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.next_subword_boundary(), Some(2));
+        assert_eq!(cursor.next_subword_boundary(), Some(5));
+    }
+
+    #[test]
+    fn match_pairs_line_on_same_line() {
+        let rope = Rope::from("a\nfoo(b)\n");
+        let mut cursor = WordCursor::new(&rope, 5);
+        assert_eq!(cursor.match_pairs_line(), Some(1));
+        let mut cursor = WordCursor::new(&rope, 3);
+        assert_eq!(cursor.match_pairs_line(), None);
+    }
+
+    #[test]
+    fn match_pairs_line_on_multiple_lines() {
+        let rope = Rope::from("fn a() {\n    b();\n}\n");
+        let mut cursor = WordCursor::new(&rope, 7);
+        assert_eq!(cursor.match_pairs_line(), Some(2));
+        let mut cursor = WordCursor::new(&rope, 18);
+        assert_eq!(cursor.match_pairs_line(), Some(0));
+    }
+
+    #[test]
+    fn hyphenated_words_should_be_one_word() {
+        let rope = Rope::from("a well-known mother-in-law");
+        let options = WordOptions {
+            hyphenated_words: true,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 0, options);
+        assert_eq!(cursor.next_boundary(), Some(2));
+        assert_eq!(cursor.next_boundary(), Some(13));
+        assert_eq!(cursor.next_boundary(), Some(26));
+        assert_eq!(cursor.prev_boundary(), Some(13));
+        let mut cursor = WordCursor::new(&rope, 2);
+        assert_eq!(cursor.next_boundary(), Some(6));
+    }
+
+    #[test]
+    fn hyphen_surrounded_by_spaces_should_be_a_token() {
+        let rope = Rope::from("a - b");
+        let options = WordOptions {
+            hyphenated_words: true,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 0, options);
+        assert_eq!(cursor.next_boundary(), Some(2));
+        assert_eq!(cursor.next_boundary(), Some(4));
+    }
+
+    #[test]
+    fn select_big_word_should_include_punctuation() {
+        let text = "foo.bar() baz";
+        let rope = Rope::from(text);
+        let (start, end) = select_big_word_at(&rope, 5);
+        assert_eq!(&text[start..end], "foo.bar()");
+        let (start, end) = select_big_word_at(&rope, 11);
+        assert_eq!(&text[start..end], "baz");
+    }
+
+    #[test]
+    fn select_big_word_on_whitespace_should_return_whitespace_run() {
+        let text = "foo   bar\n  baz";
+        let rope = Rope::from(text);
+        assert_eq!(select_big_word_at(&rope, 4), (3, 6));
+        assert_eq!(select_big_word_at(&rope, 11), (10, 12));
+    }
+
+    #[test]
+    fn bracket_match_cache_should_reuse_entries() {
+        let rope = Rope::from("fn a(b: [u8]) {\n    b\n}");
+        let mut cache = BracketMatchCache::new();
+        let mut cursor = BracketCursor::new(&rope, 0);
+        let first = cursor.matches_for_line(0, &mut cache).to_vec();
+        assert!(cache.contains(0));
+        let second = cursor.matches_for_line(0, &mut cache).to_vec();
+        assert_eq!(first, second);
+        assert_eq!(
+            first,
+            vec![
+                (4, Some(12)),
+                (8, Some(11)),
+                (11, Some(8)),
+                (12, Some(4)),
+                (14, Some(22))
+            ]
+        );
+        assert_eq!(cursor.matches_for_line(2, &mut cache), &[(22, Some(14))]);
+    }
+
+    #[test]
+    fn bracket_match_cache_should_follow_cursor_configuration() {
+        let rope = Rope::from("«a» { \"}\" }");
+        let pairs = [('«', '»')];
+        let mut cache = BracketMatchCache::new();
+        let mut cursor = BracketCursor::new(&rope, 0)
+            .with_pairs(&pairs)
+            .with_quote_skipping(true);
+        let matches = cursor.matches_for_line(0, &mut cache).to_vec();
+        assert_eq!(
+            matches,
+            vec![(0, Some(3)), (3, Some(0)), (6, Some(12)), (12, Some(6))]
+        );
+        for (offset, other) in matches {
+            let mut cursor = BracketCursor::new(&rope, offset)
+                .with_pairs(&pairs)
+                .with_quote_skipping(true);
+            assert_eq!(cursor.match_bracket(), other);
+        }
+        assert_eq!(cursor.inner.pos(), 0);
+
+        // Offsets are real ones, without the virtual text
+        let rope = Rope::from("f(x: a)");
+        let hint = 2..5;
+        let virtual_ranges = std::slice::from_ref(&hint);
+        let mut cache = BracketMatchCache::new();
+        let mut cursor = BracketCursor::new_mapped(&rope, 0, virtual_ranges);
+        assert_eq!(
+            cursor.matches_for_line(0, &mut cache),
+            &[(1, Some(3)), (3, Some(1))]
+        );
+    }
+
+    #[test]
+    fn bracket_match_cache_should_be_invalidated_on_edit() {
+        let mut cache = BracketMatchCache::new();
+        let rope = Rope::from("(a)\n{\nb\n}\nc");
+        let mut cursor = BracketCursor::new(&rope, 0);
+        for line in 0..5 {
+            cursor.matches_for_line(line, &mut cache);
+        }
+
+        // Edit of the line `b`, between the braces
+        let edited = Rope::from("(a)\n{\nbb\n}\nc");
+        cache.invalidate_line(2);
+        assert!(cache.contains(0));
+        assert!(!cache.contains(1));
+        assert!(!cache.contains(3));
+
+        let mut cursor = BracketCursor::new(&edited, 0);
+        assert_eq!(cursor.matches_for_line(1, &mut cache), &[(4, Some(9))]);
+        assert_eq!(cursor.matches_for_line(3, &mut cache), &[(9, Some(4))]);
+        assert_eq!(
+            cursor.matches_for_line(0, &mut cache),
+            &[(0, Some(2)), (2, Some(0))]
+        );
+    }
+
+    #[test]
+    fn at_word_boundary_after_word_should_be_true() {
+        let rope = Rope::from("foo bar.baz");
+        assert!(at_word_boundary(&rope, 3));
+        assert!(at_word_boundary(&rope, 7));
+        assert!(at_word_boundary(&rope, 11));
+        assert!(at_word_boundary(&rope, 0));
+        assert!(at_word_boundary(&rope, 4));
+    }
+
+    #[test]
+    fn at_word_boundary_inside_word_or_whitespace_should_be_false() {
+        let rope = Rope::from("foo   bar");
+        assert!(!at_word_boundary(&rope, 1));
+        assert!(!at_word_boundary(&rope, 4));
+        assert!(!at_word_boundary(&rope, 5));
+        assert!(!at_word_boundary(&Rope::from(""), 0));
+    }
+
+    #[test]
+    fn find_transitions_should_find_word_starts_after_spaces() {
+        let text = "The quick  brown fox, jumps";
+        let rope = Rope::from(text);
+        let starts = find_transitions(
+            &rope,
+            0..text.len(),
+            CharClassification::Space,
+            CharClassification::Other,
+        );
+        assert_eq!(starts, vec![4, 11, 17, 22]);
+
+        let starts = find_transitions(
+            &rope,
+            5..text.len(),
+            CharClassification::Space,
+            CharClassification::Other,
+        );
+        assert_eq!(starts, vec![11, 17, 22]);
+    }
+
+    #[test]
+    fn find_transitions_should_find_punctuation_after_word() {
+        let rope = Rope::from("foo.bar(baz)");
+        let found = find_transitions(
+            &rope,
+            0..rope.len(),
+            CharClassification::Other,
+            CharClassification::Punctuation,
+        );
+        assert_eq!(found, vec![3, 7, 11]);
+    }
+
+    #[test]
+    fn cursors_should_snap_mid_codepoint_offsets() {
+        // `é` spans the bytes 1..3, `日` the bytes 8..11
+        let rope = Rope::from("héllo (日)");
+        let mut cursor = WordCursor::new(&rope, 2);
+        assert_eq!(cursor.inner.pos(), 1);
+        assert_eq!(cursor.select_word(), (0, 6));
+        assert!(WordCursor::try_new(&rope, 2).is_none());
+        assert!(WordCursor::try_new(&rope, 30).is_none());
+        assert_eq!(WordCursor::new(&rope, 30).inner.pos(), rope.len());
+
+        let mut cursor = BracketCursor::new(&rope, 9);
+        assert_eq!(cursor.inner.pos(), 8);
+        assert_eq!(cursor.next_bracket(None), Some(11));
+        assert!(BracketCursor::try_new(&rope, 10).is_none());
+        assert!(BracketCursor::try_new(&rope, 11).is_some());
+    }
+
+    #[test]
+    fn next_tab_stop_should_align_to_tab_width() {
+        assert_eq!(next_tab_stop(0, 4), 4);
+        assert_eq!(next_tab_stop(3, 4), 4);
+        assert_eq!(next_tab_stop(4, 4), 8);
+        assert_eq!(next_tab_stop(7, 8), 8);
+        assert_eq!(next_tab_stop(3, 0), 4);
+    }
+
+    #[test]
+    fn column_at_should_expand_tabs_and_wide_chars() {
+        // `日本` are wide, 3 bytes each
+        let text = "ab\tc\n日本\tx\n\t日\ty";
+        let rope = Rope::from(text);
+        assert_eq!(column_at(&rope, 2, 4), 2);
+        assert_eq!(column_at(&rope, 3, 4), 4);
+        assert_eq!(column_at(&rope, 5, 4), 0);
+        assert_eq!(column_at(&rope, 11, 4), 4);
+        assert_eq!(column_at(&rope, 12, 4), 8);
+        assert_eq!(column_at(&rope, 12, 3), 6);
+        assert_eq!(column_at(&rope, 15, 4), 4);
+        assert_eq!(column_at(&rope, 18, 4), 6);
+        assert_eq!(column_at(&rope, 19, 4), 8);
+    }
+
+    #[test]
+    fn column_at_should_use_unicode_widths() {
+        // `🚀` is wide, 4 bytes, and the combining acute accent takes no column
+        let text = "🚀a\u{301}b";
+        let rope = Rope::from(text);
+        assert_eq!(column_at(&rope, 4, 4), 2);
+        assert_eq!(column_at(&rope, 5, 4), 3);
+        assert_eq!(column_at(&rope, 7, 4), 3);
+        assert_eq!(column_at(&rope, text.len(), 4), 4);
+    }
+
+    const JINJA_BLOCKS: [TemplateBlock; 2] = [
+        TemplateBlock {
+            open: "if",
+            close: "endif",
+            mid: &["elif", "else"],
+        },
+        TemplateBlock {
+            open: "for",
+            close: "endfor",
+            mid: &["else"],
+        },
+    ];
+
+    #[test]
+    fn template_block_cursor_should_skip_nested_blocks() {
+        let text = "{% if a %}{% for b in c %}{% if b %}x{% endif %}{% endfor %}{% endif %}";
+        let rope = Rope::from(text);
+        let matched = |pos| {
+            TemplateBlockCursor::new(&rope, pos, ("{%", "%}"), &JINJA_BLOCKS)
+                .match_tag()
+                .map(|(start, end)| (start, &text[start..end]))
+        };
+        assert_eq!(matched(0), Some((60, "{% endif %}")));
+        assert_eq!(matched(12), Some((48, "{% endfor %}")));
+        assert_eq!(matched(26), Some((37, "{% endif %}")));
+        assert_eq!(matched(40), Some((26, "{% if b %}")));
+        assert_eq!(matched(65), Some((0, "{% if a %}")));
+        assert_eq!(matched(36), None);
+    }
+
+    #[test]
+    fn template_block_cursor_should_match_mid_tags_and_whitespace_control() {
+        let text = "{%- if a -%}\n{% elif b %}\n{% else %}\n{%- endif %}";
+        let rope = Rope::from(text);
+        let mut cursor =
+            TemplateBlockCursor::new(&rope, 15, ("{%", "%}"), &JINJA_BLOCKS);
+        let (start, end) = cursor.match_tag().unwrap();
+        assert_eq!(&text[start..end], "{%- endif %}");
+        let mut cursor =
+            TemplateBlockCursor::new(&rope, start, ("{%", "%}"), &JINJA_BLOCKS);
+        assert_eq!(cursor.match_tag(), Some((0, 12)));
+    }
+
+    #[test]
+    fn template_block_cursor_should_not_match_mismatched_blocks() {
+        let text = "{% if a %}{% endfor %}";
+        let rope = Rope::from(text);
+        let mut cursor =
+            TemplateBlockCursor::new(&rope, 0, ("{%", "%}"), &JINJA_BLOCKS);
+        assert_eq!(cursor.match_tag(), None);
+    }
+
+    #[test]
+    fn indent_guide_range_should_follow_nested_levels() {
+        let text = "fn a() {\n    if b {\n        c();\n\n        d();\n    }\n\n    e();\n}\n\nf";
+        let rope = Rope::from(text);
+        // Guide of the function body
+        assert_eq!(indent_guide_range(&rope, 2, 0), 1..8);
+        assert_eq!(indent_guide_range(&rope, 6, 0), 1..8);
+        // Guide of the `if` body, the blank line inside it extends the guide
+        assert_eq!(indent_guide_range(&rope, 4, 4), 2..5);
+        assert_eq!(indent_guide_range(&rope, 1, 4), 1..1);
+        assert_eq!(indent_guide_range(&rope, 10, 0), 10..10);
+    }
+
+    #[test]
+    fn indent_guide_range_should_expand_tabs() {
+        let rope = Rope::from("a\n\tb\n  \tc\n  d");
+        assert_eq!(indent_guide_range(&rope, 1, 0), 1..4);
+        assert_eq!(indent_guide_range(&rope, 1, 2), 1..3);
+    }
+
+    #[test]
+    fn indent_guide_range_on_blank_line_outside_guide_should_be_empty() {
+        let rope = Rope::from("a\n    b\n\nc");
+        assert_eq!(indent_guide_range(&rope, 2, 0), 2..2);
+        assert_eq!(indent_guide_range(&rope, 1, 0), 1..2);
+    }
+
+    #[test]
+    fn boundaries_should_return_none_only_at_rope_ends() {
+        // (text, position, next_boundary, prev_boundary, end_boundary)
+        #[rustfmt::skip]
+        let matrix = [
+            ("foo bar", 0, Some(4), None, Some(3)),
+            ("foo bar", 1, Some(4), Some(0), Some(3)),
+            ("foo bar", 6, Some(7), Some(4), Some(7)),
+            ("foo bar", 7, None, Some(4), None),
+            ("a.b", 0, Some(1), None, Some(2)),
+            ("a.b", 1, Some(2), Some(0), Some(3)),
+            ("a.b", 2, Some(3), Some(1), Some(3)),
+            ("a.b", 3, None, Some(2), None),
+            ("ab  ", 0, Some(4), None, Some(2)),
+            ("ab  ", 1, Some(4), Some(0), Some(4)),
+            ("ab  ", 3, Some(4), Some(0), Some(4)),
+            ("ab  ", 4, None, Some(0), None),
+            ("x", 0, Some(1), None, Some(1)),
+            ("x", 1, None, Some(0), None),
+        ];
+        for (text, pos, next, prev, end) in matrix {
+            let rope = Rope::from(text);
+            let mut cursor = WordCursor::new(&rope, pos);
+            assert_eq!(cursor.next_boundary(), next, "next in {text:?} at {pos}");
+            assert_eq!(cursor.inner.pos(), next.unwrap_or(pos));
+            let mut cursor = WordCursor::new(&rope, pos);
+            assert_eq!(cursor.prev_boundary(), prev, "prev in {text:?} at {pos}");
+            assert_eq!(cursor.inner.pos(), prev.unwrap_or(pos));
+            let mut cursor = WordCursor::new(&rope, pos);
+            assert_eq!(cursor.end_boundary(), end, "end in {text:?} at {pos}");
+            assert_eq!(cursor.inner.pos(), end.unwrap_or(pos));
+        }
+    }
+
+    #[test]
+    fn detect_line_ending_should_find_single_line_ending() {
+        assert_eq!(detect_line_ending(&Rope::from("a\nb\n\nc")), LineEnding::Lf);
+        assert_eq!(
+            detect_line_ending(&Rope::from("a\r\nb\r\n\r\nc")),
+            LineEnding::CrLf
+        );
+        assert_eq!(detect_line_ending(&Rope::from("a\rb\r")), LineEnding::Cr);
+        assert_eq!(detect_line_ending(&Rope::from("abc")), LineEnding::Lf);
+    }
+
+    #[test]
+    fn detect_line_ending_should_find_mixed_line_endings() {
+        assert_eq!(
+            detect_line_ending(&Rope::from("a\r\nb\nc\r\n")),
+            LineEnding::Mixed
+        );
+        assert_eq!(
+            detect_line_ending(&Rope::from("a\rb\r\n")),
+            LineEnding::Mixed
+        );
+        let mut text = "line\r\n".repeat(2000);
+        text.push_str("last\n");
+        assert_eq!(detect_line_ending(&Rope::from(text)), LineEnding::Mixed);
+    }
+
+    #[test]
+    fn select_operator_should_select_punctuation_run() {
+        let text = "a != b; f => c <=> d";
+        let rope = Rope::from(text);
+        let operator =
+            |pos| select_operator(&rope, pos).map(|(start, end)| &text[start..end]);
+        assert_eq!(operator(2), Some("!="));
+        assert_eq!(operator(4), Some("!="));
+        assert_eq!(operator(11), Some("=>"));
+        assert_eq!(operator(16), Some("<=>"));
+        assert_eq!(operator(6), Some(";"));
+        assert_eq!(operator(9), None);
+    }
+
+    #[test]
+    fn next_non_blank_char_info_on_same_line() {
+        let rope = Rope::from("foo(   bar)");
+        let mut cursor = WordCursor::new(&rope, 4);
+        assert_eq!(cursor.next_non_blank_char_info(), (7, false));
+        assert_eq!(cursor.inner.pos(), 7);
+    }
+
+    #[test]
+    fn next_non_blank_char_info_on_next_line() {
+        let rope = Rope::from("foo {  \r\n\r\n  bar\n}");
+        let mut cursor = WordCursor::new(&rope, 5);
+        assert_eq!(cursor.next_non_blank_char_info(), (13, true));
+        let mut cursor = WordCursor::new(&rope, 16);
+        assert_eq!(cursor.next_non_blank_char_info(), (17, true));
+        let mut cursor = WordCursor::new(&rope, 18);
+        assert_eq!(cursor.next_non_blank_char_info(), (18, false));
+    }
+
+    #[test]
+    fn bracket_cursor_should_skip_quoted_brackets() {
+        let text = r#"{ "}" '{' "\"}" }"#;
+        let rope = Rope::from(text);
+        let mut cursor = BracketCursor::new(&rope, 0).with_quote_skipping(true);
+        assert_eq!(cursor.match_bracket(), Some(text.len() - 1));
+        assert_eq!(cursor.match_bracket(), Some(0));
+
+        let mut cursor = BracketCursor::new(&rope, 0);
+        assert_eq!(cursor.match_bracket(), Some(3));
+    }
+
+    #[test]
+    fn bracket_cursor_quote_skipping_should_be_per_line() {
+        let text = "f(\"(\",\n  g(')'))";
+        let rope = Rope::from(text);
+        let mut cursor = BracketCursor::new(&rope, 1).with_quote_skipping(true);
+        assert_eq!(cursor.match_bracket(), Some(text.len() - 1));
+        let mut cursor = BracketCursor::new(&rope, 1).with_quote_skipping(true);
+        assert_eq!(cursor.next_bracket(None), Some(10));
+        let mut cursor = BracketCursor::new(&rope, 4).with_quote_skipping(true);
+        assert_eq!(cursor.match_bracket(), None);
+    }
+
+    #[test]
+    fn word_with_trailing_space_should_include_whitespace() {
+        let text = "foo bar";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.word_with_trailing_space(), (0, 4));
+        let mut cursor = WordCursor::new(&rope, 1);
+        assert_eq!(cursor.word_with_trailing_space(), (1, 4));
+        let mut cursor = WordCursor::new(&rope, 4);
+        assert_eq!(cursor.word_with_trailing_space(), (4, 7));
+    }
+
+    #[test]
+    fn word_with_trailing_space_should_stop_at_line_end() {
+        let text = "foo.bar  \nbaz";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.word_with_trailing_space(), (0, 3));
+        let mut cursor = WordCursor::new(&rope, 3);
+        assert_eq!(cursor.word_with_trailing_space(), (3, 4));
+        let mut cursor = WordCursor::new(&rope, 4);
+        assert_eq!(cursor.word_with_trailing_space(), (4, 9));
+        let mut cursor = WordCursor::new(&rope, 8);
+        assert_eq!(cursor.word_with_trailing_space(), (8, 9));
+        assert_eq!(cursor.inner.pos(), 8);
+    }
+
+    const RAINBOW_PAIRS: [(char, char); 3] = [('(', ')'), ('{', '}'), ('[', ']')];
+
+    #[test]
+    fn bracket_tokens_should_report_nesting_depth() {
+        let rope = Rope::from("({[]})");
+        let tokens: Vec<_> =
+            bracket_tokens(&rope, 0..rope.len(), &RAINBOW_PAIRS).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, '(', 0),
+                (1, '{', 1),
+                (2, '[', 2),
+                (3, ']', 2),
+                (4, '}', 1),
+                (5, ')', 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn bracket_tokens_should_go_negative_on_unmatched_closing() {
+        let rope = Rope::from("a) (b)] c");
+        let depths: Vec<_> = bracket_tokens(&rope, 0..rope.len(), &RAINBOW_PAIRS)
+            .map(|(_, _, depth)| depth)
+            .collect();
+        assert_eq!(depths, vec![-1, -1, -1, -2]);
+        let depths: Vec<_> = bracket_tokens(&rope, 3..rope.len(), &RAINBOW_PAIRS)
+            .map(|(_, _, depth)| depth)
+            .collect();
+        assert_eq!(depths, vec![0, 0, -1]);
+    }
+
+    #[test]
+    fn parent_line_should_find_less_indented_line() {
+        let rope = Rope::from(PYTHON_BLOCKS);
+        let parent = |text: &str| {
+            let pos = PYTHON_BLOCKS.find(text).unwrap();
+            parent_line(&rope, pos).map(|offset| rope.line_of_offset(offset))
+        };
+        assert_eq!(parent("a()"), Some(1));
+        assert_eq!(parent("if"), Some(0));
+        assert_eq!(parent("c()"), Some(0));
+        assert_eq!(parent("d()"), None);
+    }
+
+    #[test]
+    fn parent_line_should_skip_blank_lines() {
+        let text = "class A:\n    def f():\n\n  \n\n        pass";
+        let rope = Rope::from(text);
+        assert_eq!(parent_line(&rope, text.len()), Some(9));
+        assert_eq!(parent_line(&rope, 25), Some(0));
+    }
+
+    #[test]
+    fn same_word_inside_identifier_should_be_true() {
+        let rope = Rope::from("let some_ident = 1;");
+        assert!(same_word(&rope, 4, 14));
+        assert!(same_word(&rope, 14, 4));
+        assert!(same_word(&rope, 8, 8));
+        assert!(same_word(&rope, 14, 14));
+    }
+
+    #[test]
+    fn same_word_across_boundary_should_be_false() {
+        let rope = Rope::from("let some_ident = a.b;");
+        assert!(!same_word(&rope, 1, 5));
+        assert!(!same_word(&rope, 17, 19));
+        assert!(!same_word(&rope, 15, 15));
+    }
+
+    #[test]
+    fn number_separators_should_keep_numbers_whole() {
+        let rope = Rope::from("x = 1,000,000 + 1_000;");
+        let options = WordOptions {
+            number_separators: &[',', '_'],
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 4, options);
+        assert_eq!(cursor.next_boundary(), Some(14));
+        assert_eq!(cursor.next_boundary(), Some(16));
+        assert_eq!(cursor.next_boundary(), Some(21));
+        assert_eq!(cursor.prev_boundary(), Some(16));
+        assert_eq!(cursor.prev_boundary(), Some(14));
+        assert_eq!(cursor.prev_boundary(), Some(4));
+
+        let mut cursor = WordCursor::new(&rope, 4);
+        assert_eq!(cursor.next_boundary(), Some(5));
+    }
+
+    #[test]
+    fn number_separator_without_following_digit_should_be_punctuation() {
+        let rope = Rope::from("f(1, 2,a)");
+        let options = WordOptions {
+            number_separators: &[','],
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 2, options);
+        assert_eq!(cursor.next_boundary(), Some(3));
+        assert_eq!(cursor.next_boundary(), Some(5));
+        assert_eq!(cursor.next_boundary(), Some(6));
+        assert_eq!(cursor.next_boundary(), Some(7));
+    }
+
+    #[test]
+    fn fold_range_should_keep_opening_line() {
+        let text = "impl A {\r\n    fn a() {}\r\n}\r\n";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 7);
+        let range = cursor.fold_range().unwrap();
+        assert_eq!(&text[range], "\r\n    fn a() {}\r\n");
+        assert_eq!(cursor.inner.pos(), 7);
+    }
+
+    #[test]
+    fn fold_range_of_single_line_pair_should_be_none() {
+        let rope = Rope::from("fn a() {}\n{\n");
+        assert_eq!(WordCursor::new(&rope, 7).fold_range(), None);
+        assert_eq!(WordCursor::new(&rope, 4).fold_range(), None);
+        assert_eq!(WordCursor::new(&rope, 8).fold_range(), None);
+        assert_eq!(WordCursor::new(&rope, 10).fold_range(), None);
+    }
+
+    #[test]
+    fn unicode_currency_and_math_symbols_should_be_punctuation() {
+        for c in ['€', '£', '¥', '±', '×', '÷', '∑', '≠', '→'] {
+            assert_eq!(
+                get_unicode_char_property(c),
+                CharClassification::Punctuation
+            );
+        }
+        assert_eq!(get_unicode_char_property('é'), CharClassification::Other);
+
+        let options = WordOptions {
+            unicode: true,
+            ..Default::default()
+        };
+        let rope = Rope::from("€100 a×b");
+        let mut cursor = WordCursor::with_options(&rope, 0, options);
+        assert_eq!(cursor.next_boundary(), Some(3));
+        assert_eq!(cursor.next_boundary(), Some(7));
+        assert_eq!(cursor.next_boundary(), Some(8));
+        assert_eq!(cursor.next_boundary(), Some(10));
+
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.next_boundary(), Some(7));
+    }
+
+    #[test]
+    fn next_boundary_classified_should_report_landing_class() {
+        let rope = Rope::from("Hello world.foo ");
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(
+            cursor.next_boundary_classified(),
+            Some((6, CharClassification::Other))
+        );
+        assert_eq!(
+            cursor.next_boundary_classified(),
+            Some((11, CharClassification::Punctuation))
+        );
+        assert_eq!(
+            cursor.next_boundary_classified(),
+            Some((12, CharClassification::Other))
+        );
+        assert_eq!(
+            cursor.next_boundary_classified(),
+            Some((16, CharClassification::Space))
+        );
+        assert_eq!(cursor.next_boundary_classified(), None);
+    }
+
+    #[test]
+    fn delete_match_range_should_include_both_brackets() {
+        let text = "x = {abc};";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 4);
+        let range = cursor.delete_match_range().unwrap();
+        assert_eq!(&text[range.clone()], "{abc}");
+        assert_eq!(cursor.inner.pos(), 4);
+        let mut cursor = WordCursor::new(&rope, 8);
+        assert_eq!(cursor.delete_match_range(), Some(range.clone()));
+
+        let mut edited = rope.clone();
+        edited.edit(range, "");
+        assert_eq!(String::from(edited), "x = ;");
+    }
+
+    #[test]
+    fn delete_match_range_without_pair_should_be_none() {
+        let rope = Rope::from("{abc");
+        assert_eq!(WordCursor::new(&rope, 0).delete_match_range(), None);
+        assert_eq!(WordCursor::new(&rope, 1).delete_match_range(), None);
+        assert_eq!(WordCursor::new(&rope, 1).matched_range(), None);
+    }
+
+    #[test]
+    fn enclosing_pair_should_return_innermost_pair() {
+        let text = "( { x } )";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 4);
+        assert_eq!(cursor.enclosing_pair(), Some((2, 6)));
+        assert_eq!(cursor.inner.pos(), 4);
+        assert_eq!(WordCursor::new(&rope, 1).enclosing_pair(), Some((0, 8)));
+        assert_eq!(WordCursor::new(&rope, 7).enclosing_pair(), Some((0, 8)));
+    }
+
+    #[test]
+    fn enclosing_pair_should_skip_nested_pairs_of_any_kind() {
+        let rope = Rope::from("{ (a) x [b] }");
+        assert_eq!(WordCursor::new(&rope, 6).enclosing_pair(), Some((0, 12)));
+        let rope = Rope::from("{ [ x } ]");
+        assert_eq!(WordCursor::new(&rope, 4).enclosing_pair(), None);
+        // `( ]` is not a nested pair, the brackets are malformed
+        let rope = Rope::from("{ ( ] x }");
+        assert_eq!(WordCursor::new(&rope, 6).enclosing_pair(), None);
+        let rope = Rope::from("{ x [ ) }");
+        assert_eq!(WordCursor::new(&rope, 2).enclosing_pair(), None);
+        let rope = Rope::from("x ( y");
+        assert_eq!(WordCursor::new(&rope, 0).enclosing_pair(), None);
+    }
+
+    #[test]
+    fn token_at_should_use_identifier_spec() {
+        let text = "let été_2 = foo.bar;";
+        let rope = Rope::from(text);
+        assert_eq!(&text[token_at(&rope, 6, TokenSpec::IDENTIFIER)], "été_2");
+        assert_eq!(&text[token_at(&rope, 16, TokenSpec::IDENTIFIER)], "foo");
+        assert_eq!(token_at(&rope, 12, TokenSpec::IDENTIFIER), 12..12);
+    }
+
+    #[test]
+    fn token_at_should_use_number_spec() {
+        let text = "a = 3.14 + 0x10;";
+        let rope = Rope::from(text);
+        assert_eq!(&text[token_at(&rope, 5, TokenSpec::NUMBER)], "3.14");
+        assert_eq!(&text[token_at(&rope, 15, TokenSpec::NUMBER)], "0x10");
+
+        let hex = [TokenClass::HexDigit, TokenClass::Char('#')];
+        let text = "color: #ff00aa;";
+        let rope = Rope::from(text);
+        assert_eq!(&text[token_at(&rope, 9, TokenSpec::new(&hex))], "#ff00aa");
+    }
+
+    #[test]
+    fn boundaries_should_terminate_monotonically() {
+        let texts = [
+            "",
+            "a",
+            "foo bar",
+            "  leading and trailing  ",
+            "a.b(c) -> d;\r\n\r\n  e\n",
+            "été 日本 \u{a0}x",
+            "a\u{1}b\u{7f}",
+        ];
+        let string_ranges = [2..5, 8..10];
+        let options = [
+            WordOptions::default(),
+            WordOptions {
+                unicode: true,
+                hyphenated_words: true,
+                string_ranges: &string_ranges,
+                ..Default::default()
+            },
+        ];
+        for text in texts {
+            let rope = Rope::from(text);
+            for options in options {
+                let mut cursor = WordCursor::with_options(&rope, 0, options);
+                let mut last = 0;
+                while let Some(next) = cursor.next_boundary() {
+                    assert!(next > last, "next_boundary stuck in {text:?}");
+                    last = next;
+                }
+                assert_eq!(last, text.len());
+
+                let mut cursor =
+                    WordCursor::with_options(&rope, text.len(), options);
+                let mut last = text.len();
+                while let Some(prev) = cursor.prev_boundary() {
+                    assert!(prev < last, "prev_boundary stuck in {text:?}");
+                    last = prev;
+                }
+                assert_eq!(last, 0);
+
+                let mut cursor = WordCursor::with_options(&rope, 0, options);
+                let mut last = 0;
+                while let Some(next) = cursor.next_visual_word() {
+                    assert!(next > last, "next_visual_word stuck in {text:?}");
+                    last = next;
+                }
+                assert_eq!(last, text.len());
+            }
+        }
+    }
+
+    #[test]
+    fn dedent_target_should_snap_to_parent_indentation() {
+        let text =
+            "class A:\n    def f(self):\n\n        return 1\n    x = 2\ny = 3";
+        let rope = Rope::from(text);
+        let target = |s: &str| dedent_target(&rope, text.find(s).unwrap());
+        assert_eq!(target("return"), Some(4));
+        assert_eq!(target("def"), Some(0));
+        assert_eq!(target("x = 2"), Some(0));
+        assert_eq!(target("y = 3"), None);
+    }
+
+    #[test]
+    fn bracket_pair_scanner_should_match_batch_result() {
+        let text = "fn a(b: [u8]) {\n    if c { d(); }\n}\n(";
+        let rope = Rope::from(text);
+        let mut scanner = BracketPairScanner::new(0..rope.len());
+        let mut pairs = Vec::new();
+        while let Some(pair) = scanner.next_pair(&rope) {
+            assert!(!pairs.contains(&pair));
+            pairs.push(pair);
+        }
+        assert!(scanner.is_done());
+        assert_eq!(scanner.next_pair(&rope), None);
+
+        let mut expected: Vec<_> = text
+            .char_indices()
+            .filter(|(_, c)| matches!(c, '(' | '[' | '{'))
+            .filter_map(|(offset, _)| {
+                let close = WordCursor::new(&rope, offset).match_pairs()?;
+                Some((offset, close))
+            })
+            .collect();
+        expected.sort_by_key(|(_, close)| *close);
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn bracket_pair_scanner_should_resume_where_it_left_off() {
+        let rope = Rope::from("(a)[b]{c}");
+        let mut scanner = BracketPairScanner::new(0..rope.len());
+        assert_eq!(scanner.next_pair(&rope), Some((0, 2)));
+        assert!(!scanner.is_done());
+        let mut resumed = scanner.clone();
+        assert_eq!(resumed.next_pair(&rope), Some((3, 5)));
+        assert_eq!(resumed.next_pair(&rope), Some((6, 8)));
+        assert!(resumed.is_done());
+
+        let mut scanner = BracketPairScanner::new(1..6);
+        assert_eq!(scanner.next_pair(&rope), Some((3, 5)));
+        assert_eq!(scanner.next_pair(&rope), None);
+    }
+
+    #[test]
+    fn line_kind_should_classify_lines() {
+        let text = "fn a() {\n  \t\n    // comment\n    b(); // trailing\n\n#x\n}";
+        let rope = Rope::from(text);
+        let kind = |s: &str| line_kind(&rope, text.find(s).unwrap(), "//");
+        assert_eq!(kind("fn"), LineKind::Code);
+        assert_eq!(kind("  \t"), LineKind::Blank);
+        assert_eq!(kind("// comment"), LineKind::Comment);
+        assert_eq!(kind("b();"), LineKind::Code);
+        let empty = text.find("\n\n").unwrap() + 1;
+        assert_eq!(line_kind(&rope, empty, "//"), LineKind::Blank);
+        let hash = text.find("#x").unwrap();
+        assert_eq!(line_kind(&rope, hash, "//"), LineKind::Code);
+        assert_eq!(line_kind(&rope, hash, "#"), LineKind::Comment);
+    }
+
+    #[test]
+    fn match_pairs_text_should_include_both_brackets() {
+        let text = "{ }";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 2);
+        assert_eq!(cursor.match_pairs_text().unwrap(), "{ }");
+        assert_eq!(cursor.inner.pos(), 2);
+        let mut cursor = WordCursor::new(&rope, 1);
+        assert_eq!(cursor.match_pairs_text(), None);
+    }
+
+    #[test]
+    fn next_visual_word_should_include_attached_punctuation() {
+        let rope = Rope::from("foo, bar) (baz)\n  qux");
+        let mut cursor = WordCursor::new(&rope, 0);
+        let offsets: Vec<_> =
+            std::iter::from_fn(|| cursor.next_visual_word()).collect();
+        assert_eq!(offsets, vec![4, 9, 11, 15, 21]);
+    }
+
+    #[test]
+    fn next_visual_word_at_end_should_be_none() {
+        let rope = Rope::from("foo");
+        let mut cursor = WordCursor::new(&rope, 1);
+        assert_eq!(cursor.next_visual_word(), Some(3));
+        assert_eq!(cursor.next_visual_word(), None);
+        assert_eq!(cursor.inner.pos(), 3);
     }
 
     #[test]
-    fn get_next_non_blank_char_should_return_current_position_on_non_blank_char() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 3);
-        let char_position = cursor.next_non_blank_char();
-        assert_eq!(char_position, 3);
+    fn cursor_at_end_of_rope_should_not_panic() {
+        let text = "foo (bar) baz";
+        let rope = Rope::from(text);
+        let len = rope.len();
+        let cursor = || WordCursor::new(&rope, len);
+
+        assert_eq!(cursor().prev_boundary(), Some(10));
+        assert_eq!(cursor().prev_deletion_boundary(), Some(10));
+        assert_eq!(cursor().prev_code_boundary(), 10);
+        assert_eq!(cursor().next_code_boundary(), len);
+        assert_eq!(cursor().next_non_blank_char(), len);
+        assert_eq!(cursor().next_non_blank_char_info(), (len, false));
+        assert_eq!(cursor().next_boundary(), None);
+        assert_eq!(cursor().next_boundary_classified(), None);
+        assert_eq!(cursor().next_visual_word(), None);
+        assert_eq!(cursor().next_subword_boundary(), None);
+        assert_eq!(cursor().end_boundary(), None);
+        assert_eq!(cursor().match_pairs(), None);
+        assert_eq!(cursor().match_pairs_info(), None);
+        assert_eq!(cursor().matched_range(), None);
+        assert_eq!(cursor().delete_match_range(), None);
+        assert_eq!(cursor().match_pairs_text(), None);
+        assert_eq!(cursor().match_pairs_line(), None);
+        assert_eq!(cursor().fold_range(), None);
+        assert_eq!(cursor().enclosing_pair(), None);
+        assert_eq!(cursor().enclosing_pair_of('('), None);
+        assert_eq!(cursor().next_unmatched(')'), None);
+        assert_eq!(cursor().previous_unmatched('('), None);
+        assert_eq!(cursor().select_word(), (10, len));
+        assert_eq!(cursor().select_big_word(), (10, len));
+        assert_eq!(cursor().word_with_trailing_space(), (len, len));
+        assert_eq!(cursor().select_word_reversed(), (len, 10));
+        assert_eq!(cursor().match_word_pair(&[("foo", "baz")]), Some((0, 3)));
+
+        let mut cursor = BracketCursor::new(&rope, len);
+        assert_eq!(cursor.next_bracket(None), None);
+        assert_eq!(cursor.match_bracket(), None);
+        assert_eq!(cursor.prev_bracket(None), Some(8));
     }
 
     #[test]
-    fn should_get_end_boundary() {
-        let rope = Rope::from("Hello world");
-        let mut cursor = WordCursor::new(&rope, 3);
-        let end_boundary = cursor.end_boundary();
-        assert_eq!(end_boundary, Some(5));
+    fn mapped_bracket_cursor_should_return_real_offsets() {
+        // Real buffer: `{ a(b) }`, with virtual text `(x) ` at the start and
+        // `: (i32)` after `b`
+        let rope = Rope::from("(x) { a(b: (i32)) }");
+        let virtual_ranges = [0..4, 9..16];
+        let mut cursor = BracketCursor::new_mapped(&rope, 0, &virtual_ranges);
+        assert_eq!(cursor.match_bracket(), Some(7));
+        let mut cursor = BracketCursor::new_mapped(&rope, 3, &virtual_ranges);
+        assert_eq!(cursor.match_bracket(), Some(5));
+        let mut cursor = BracketCursor::new_mapped(&rope, 5, &virtual_ranges);
+        assert_eq!(cursor.match_bracket(), Some(3));
+        let mut cursor = BracketCursor::new_mapped(&rope, 3, &virtual_ranges);
+        assert_eq!(cursor.next_bracket(None), Some(5));
+        assert_eq!(cursor.next_bracket(None), Some(7));
+        assert_eq!(cursor.prev_bracket(None), Some(5));
     }
 
     #[test]
-    fn should_get_next_unmatched_char() {
-        let rope = Rope::from("hello { world");
-        let mut cursor = WordCursor::new(&rope, 0);
-        let position = cursor.next_unmatched('{');
-        assert_eq!(position, Some(7));
+    fn word_occurrences_should_find_whole_words() {
+        let text = "let foo = foo + foobar + barfoo + foo;";
+        let rope = Rope::from(text);
+        let occurrences = word_occurrences(&rope, 5, 0..rope.len(), true);
+        assert_eq!(occurrences.len(), 3);
+        for (start, end) in &occurrences {
+            assert_eq!(&text[*start..*end], "foo");
+        }
+        assert_eq!(occurrences, vec![(4, 7), (10, 13), (34, 37)]);
+
+        let occurrences = word_occurrences(&rope, 5, 0..rope.len(), false);
+        assert_eq!(occurrences.len(), 5);
     }
 
     #[test]
-    fn should_get_next_unmatched_char_witch_matched_chars() {
-        let rope = Rope::from("hello {} world }");
+    fn word_occurrences_should_stay_in_search_range() {
+        let text = "foo foo foo";
+        let rope = Rope::from(text);
+        assert_eq!(word_occurrences(&rope, 0, 2..9, true), vec![(4, 7)]);
+        assert_eq!(word_occurrences(&rope, 3, 0..rope.len(), true).len(), 3);
+        assert!(word_occurrences(&Rope::from("a + b"), 2, 0..5, true).is_empty());
+    }
+
+    #[test]
+    fn would_match_if_typed_should_find_closed_bracket() {
+        let rope = Rope::from("f(a, (b), c");
+        assert_eq!(would_match_if_typed(&rope, 11, ')'), Some(1));
+        assert_eq!(would_match_if_typed(&rope, 8, ')'), Some(1));
+        assert_eq!(would_match_if_typed(&rope, 7, ')'), Some(5));
+        assert_eq!(would_match_if_typed(&rope, 0, ']'), None);
+        assert_eq!(would_match_if_typed(&rope, 11, 'a'), None);
+    }
+
+    #[test]
+    fn would_match_if_typed_should_find_opened_bracket() {
+        let rope = Rope::from("a, b) + (c)");
+        assert_eq!(would_match_if_typed(&rope, 0, '('), Some(4));
+        assert_eq!(would_match_if_typed(&rope, 5, '('), None);
+    }
+
+    #[test]
+    fn logical_line_range_should_join_continued_lines() {
+        let text = "a=1\necho foo \\\n  bar\nb=2";
+        let rope = Rope::from(text);
+        let line = |pos| {
+            let (start, end) = logical_line_range(&rope, pos, '\\');
+            &text[start..end]
+        };
+        assert_eq!(line(6), "echo foo \\\n  bar");
+        assert_eq!(line(17), "echo foo \\\n  bar");
+        assert_eq!(line(0), "a=1");
+        assert_eq!(line(text.len()), "b=2");
+    }
+
+    #[test]
+    fn logical_line_range_should_ignore_escaped_continuation() {
+        let text = "echo a\\\\\r\necho b\\\\\\\r\necho c";
+        let rope = Rope::from(text);
+        let (start, end) = logical_line_range(&rope, 0, '\\');
+        assert_eq!(&text[start..end], "echo a\\\\");
+        let (start, end) = logical_line_range(&rope, 11, '\\');
+        assert_eq!(&text[start..end], "echo b\\\\\\\r\necho c");
+    }
+
+    #[test]
+    fn indent_columns_should_expand_tabs() {
+        let rope = Rope::from("    a\n\t\tb\n \t  c\n\t \td\ne");
+        let columns = |line, tab_width| {
+            indent_columns(&rope, rope.offset_of_line(line), tab_width)
+        };
+        assert_eq!(columns(0, 4), 4);
+        assert_eq!(columns(1, 4), 8);
+        assert_eq!(columns(1, 2), 4);
+        assert_eq!(columns(2, 4), 6);
+        assert_eq!(columns(3, 4), 8);
+        assert_eq!(columns(3, 8), 16);
+        assert_eq!(columns(4, 4), 0);
+    }
+
+    #[test]
+    fn keyword_bracket_cursor_should_match_mixed_case() {
+        let text = "Begin\n  BEGIN x; End;\n  APPEND y;\nEND";
+        let rope = Rope::from(text);
+        let pairs = [("begin", "end")];
+        let matched = |pos| {
+            KeywordBracketCursor::new(&rope, pos, &pairs)
+                .with_case_insensitive(true)
+                .match_keyword()
+        };
+        assert_eq!(matched(0), Some((34, 37)));
+        assert_eq!(matched(35), Some((0, 5)));
+        assert_eq!(matched(8), Some((17, 20)));
+        assert_eq!(matched(28), None);
+
+        let mut cursor = KeywordBracketCursor::new(&rope, 0, &pairs);
+        assert_eq!(cursor.match_keyword(), None);
+    }
+
+    #[test]
+    fn select_word_on_punctuation_run_should_select_whole_run() {
+        let text = "a ==== b";
+        let rope = Rope::from(text);
+        for pos in 2..6 {
+            let mut cursor = WordCursor::new(&rope, pos);
+            assert_eq!(cursor.select_word(), (2, 6));
+        }
+        let mut cursor = WordCursor::new(&rope, 6);
+        assert_eq!(cursor.select_word(), (6, 6));
+    }
+
+    #[test]
+    fn select_word_on_mixed_punctuation_should_stop_at_change() {
+        let rope = Rope::from("a =!= b");
+        assert_eq!(WordCursor::new(&rope, 2).select_word(), (2, 3));
+        assert_eq!(WordCursor::new(&rope, 3).select_word(), (3, 4));
+        assert_eq!(WordCursor::new(&rope, 4).select_word(), (4, 5));
+    }
+
+    #[test]
+    fn owned_cursor_should_own_its_rope() {
+        let owned = OwnedCursor::new("violet are blue", 9);
+        assert_eq!(owned.word_cursor().select_word(), (7, 10));
+        let mut cursor = owned.word_cursor();
+        assert_eq!(cursor.next_boundary(), Some(11));
+        assert_eq!(owned.word_cursor().prev_boundary(), Some(7));
+        assert_eq!(owned.text().len(), 15);
+
+        let owned = OwnedCursor::new("f(a[0])", 1);
+        assert_eq!(owned.bracket_cursor().match_bracket(), Some(6));
+        assert_eq!(owned.bracket_cursor().next_bracket(None), Some(3));
+    }
+
+    #[test]
+    fn cursors_from_str_should_own_their_rope() {
+        let owned = WordCursor::from_str("violet are blue", 9);
+        assert_eq!(owned.word_cursor().select_word(), (7, 10));
+        assert_eq!(owned.text().len(), 15);
+
+        let owned = BracketCursor::from_str("{ \"}\" }", 0);
+        let mut cursor = owned.bracket_cursor().with_quote_skipping(true);
+        assert_eq!(cursor.match_bracket(), Some(6));
+    }
+
+    #[test]
+    fn join_point_should_collapse_whitespace_to_a_space() {
+        let text = "let a =\t\n    1;\r\n\tb";
+        let rope = Rope::from(text);
+        let (range, replacement) = join_point(&rope, 0);
+        assert_eq!(range, 7..13);
+        assert_eq!(replacement, " ");
+        let mut joined = rope.clone();
+        joined.edit(range, replacement);
+        assert_eq!(String::from(joined), "let a = 1;\r\n\tb");
+
+        let (range, replacement) = join_point(&rope, 13);
+        assert_eq!(&text[range], "\r\n\t");
+        assert_eq!(replacement, " ");
+        assert_eq!(join_point(&rope, 17), (19..19, String::new()));
+    }
+
+    #[test]
+    fn join_point_with_blank_line_should_not_add_space() {
+        let text = "foo\n   \nbar";
+        let rope = Rope::from(text);
+        assert_eq!(join_point(&rope, 0), (3..7, String::new()));
+        assert_eq!(join_point(&rope, 4), (4..8, String::new()));
+    }
+
+    #[test]
+    fn identifier_style_should_detect_each_style() {
+        let style = |text: &str| identifier_style(&Rope::from(text), 0..text.len());
+        assert_eq!(style("fooBarBaz"), IdentifierStyle::CamelCase);
+        assert_eq!(style("parseHTTP2Request"), IdentifierStyle::CamelCase);
+        assert_eq!(style("FooBar"), IdentifierStyle::PascalCase);
+        assert_eq!(style("HTTPServer"), IdentifierStyle::PascalCase);
+        assert_eq!(style("foo_bar_2"), IdentifierStyle::SnakeCase);
+        assert_eq!(style("foo"), IdentifierStyle::SnakeCase);
+        assert_eq!(style("FOO_BAR"), IdentifierStyle::ScreamingSnake);
+        assert_eq!(style("MAX"), IdentifierStyle::ScreamingSnake);
+        assert_eq!(style("foo-bar"), IdentifierStyle::KebabCase);
+    }
+
+    #[test]
+    fn identifier_style_should_detect_mixed_styles() {
+        let style = |text: &str| identifier_style(&Rope::from(text), 0..text.len());
+        assert_eq!(style("foo_Bar"), IdentifierStyle::Mixed);
+        assert_eq!(style("foo-bar_baz"), IdentifierStyle::Mixed);
+        assert_eq!(style("Foo-Bar"), IdentifierStyle::Mixed);
+    }
+
+    #[test]
+    fn convert_identifier_should_split_acronyms() {
+        let rope = Rope::from("getHTTPResponse");
+        let convert = |to| convert_identifier(&rope, 0..rope.len(), to);
+        assert_eq!(convert(IdentifierStyle::SnakeCase), "get_http_response");
+        assert_eq!(
+            convert(IdentifierStyle::ScreamingSnake),
+            "GET_HTTP_RESPONSE"
+        );
+        assert_eq!(convert(IdentifierStyle::KebabCase), "get-http-response");
+        assert_eq!(convert(IdentifierStyle::PascalCase), "GetHttpResponse");
+        assert_eq!(convert(IdentifierStyle::Mixed), "getHTTPResponse");
+    }
+
+    #[test]
+    fn convert_identifier_should_keep_digits_with_previous_subword() {
+        let rope = Rope::from("utf8_decoder_v2");
+        let convert = |to| convert_identifier(&rope, 0..rope.len(), to);
+        assert_eq!(convert(IdentifierStyle::CamelCase), "utf8DecoderV2");
+        assert_eq!(convert(IdentifierStyle::KebabCase), "utf8-decoder-v2");
+    }
+
+    #[test]
+    fn first_mismatch_from_should_find_wrong_closing_bracket() {
+        let pairs = [('(', ')'), ('[', ']'), ('{', '}')];
+        let rope = Rope::from("{ ) }");
+        assert_eq!(first_mismatch_from(&rope, 0, &pairs), Some(2));
+
+        let rope = Rope::from("{ (a) [b] }");
+        assert_eq!(first_mismatch_from(&rope, 0, &pairs), None);
+
+        // Scanning from inside a block sees its closing bracket as extra
+        let rope = Rope::from("{ a } ]");
+        assert_eq!(first_mismatch_from(&rope, 1, &pairs), Some(4));
+    }
+
+    #[test]
+    fn text_object_inside_any_should_select_nearest_enclosing_pair() {
+        let pairs = [('(', ')'), ('[', ']'), ('{', '}')];
+        let rope = Rope::from("[a (b) c]");
+        assert_eq!(text_object_inside_any(&rope, 2, &pairs), Some((1, 8)));
+        assert_eq!(text_object_inside_any(&rope, 7, &pairs), Some((1, 8)));
+        assert_eq!(text_object_inside_any(&rope, 4, &pairs), Some((4, 5)));
+
+        let rope = Rope::from("{ [ x } ]");
+        assert_eq!(text_object_inside_any(&rope, 4, &pairs), None);
+        assert_eq!(text_object_inside_any(&rope, 0, &pairs), None);
+
+        // Interleaved brackets are not nested pairs
+        let rope = Rope::from("[a (b] c)");
+        assert_eq!(text_object_inside_any(&rope, 1, &pairs), None);
+        assert_eq!(text_object_inside_any(&rope, 7, &pairs), None);
+        let rope = Rope::from("{ x (y] }");
+        assert_eq!(text_object_inside_any(&rope, 2, &pairs), None);
+    }
+
+    #[test]
+    fn foldable_ranges_should_return_nested_multi_line_pairs() {
+        let text = concat!(
+            "fn a() {\n",
+            "    if b {\n",
+            "        c(d);\n",
+            "    }\n",
+            "    e([\n",
+            "        1,\n",
+            "    ]);\n",
+            "}\n",
+        );
+        let rope = Rope::from(text);
+        let ranges = foldable_ranges(&rope, &[('(', ')'), ('[', ']'), ('{', '}')]);
+        // `e([` opens two pairs on the same line, only the outermost one is kept
+        assert_eq!(ranges, vec![8..67, 19..38, 47..64]);
+        assert_eq!(&text[ranges[1].end..=ranges[1].end], "}");
+        assert_eq!(&text[ranges[2].end..=ranges[2].end], ")");
+    }
+
+    #[test]
+    fn backspace_deletes_pair_should_only_match_empty_pairs() {
+        let pairs = [('(', ')'), ('[', ']')];
+        let rope = Rope::from("a() b[] c(d) e(]");
+        assert_eq!(backspace_deletes_pair(&rope, 2, &pairs), Some(1..3));
+        assert_eq!(backspace_deletes_pair(&rope, 6, &pairs), Some(5..7));
+        assert_eq!(backspace_deletes_pair(&rope, 10, &pairs), None);
+        assert_eq!(backspace_deletes_pair(&rope, 15, &pairs), None);
+        assert_eq!(backspace_deletes_pair(&rope, 0, &pairs), None);
+    }
+
+    #[test]
+    fn next_prose_boundary_should_only_stop_at_sentence_end() {
+        let rope = Rope::from("First, (we) read 3.14; then stop?! Next");
         let mut cursor = WordCursor::new(&rope, 0);
-        let position = cursor.next_unmatched('}');
-        assert_eq!(position, Some(16));
+        let mut boundaries = Vec::new();
+        while let Some(boundary) = cursor.next_prose_boundary() {
+            boundaries.push(boundary);
+        }
+        assert_eq!(boundaries, vec![7, 12, 17, 23, 28, 32, 35, 39]);
     }
 
     #[test]
-    fn should_get_previous_unmatched_char() {
-        let rope = Rope::from("hello { world");
-        let mut cursor = WordCursor::new(&rope, 12);
-        let position = cursor.previous_unmatched('{');
-        assert_eq!(position, Some(6));
+    fn argument_ranges_should_split_top_level_commas() {
+        let text = "foo(a, b(c, d), e)";
+        let rope = Rope::from(text);
+        let ranges = argument_ranges(&rope, 5);
+        assert_eq!(ranges, vec![4..5, 7..14, 16..17]);
+        assert_eq!(&text[ranges[1].clone()], "b(c, d)");
+        // Inside the nested call, its own arguments are returned
+        assert_eq!(argument_ranges(&rope, 10), vec![9..10, 12..13]);
+
+        let rope = Rope::from("foo(a, ) bar()");
+        assert_eq!(argument_ranges(&rope, 4), vec![4..5]);
+        assert!(argument_ranges(&rope, 13).is_empty());
+        assert!(argument_ranges(&rope, 0).is_empty());
     }
 
     #[test]
-    fn should_get_previous_unmatched_char_with_inner_matched_chars() {
-        let rope = Rope::from("{hello {} world");
-        let mut cursor = WordCursor::new(&rope, 10);
-        let position = cursor.previous_unmatched('{');
-        assert_eq!(position, Some(0));
+    fn argument_ranges_should_not_split_quoted_commas() {
+        let text = r#"f("a,b", c, ',', 'd')"#;
+        let rope = Rope::from(text);
+        let ranges = argument_ranges(&rope, 3);
+        let arguments: Vec<_> = ranges.iter().map(|r| &text[r.clone()]).collect();
+        assert_eq!(arguments, vec![r#""a,b""#, "c", "','", "'d'"]);
     }
 
     #[test]
-    fn should_match_pair_forward() {
-        let text = "{ }";
+    fn keyword_bracket_cursor_should_pair_nested_blocks() {
+        let text = "begin a begin b end c end";
+        let rope = Rope::from(text);
+        let pairs = [("begin", "end")];
+        let matched =
+            |pos| KeywordBracketCursor::new(&rope, pos, &pairs).match_keyword();
+        assert_eq!(matched(0), Some((22, 25)));
+        assert_eq!(matched(8), Some((16, 19)));
+        assert_eq!(matched(17), Some((8, 13)));
+        assert_eq!(matched(23), Some((0, 5)));
+    }
+
+    #[test]
+    fn keyword_bracket_cursor_should_ignore_keywords_inside_words() {
+        let text = "begin appendix beginner end_time end";
         let rope = Rope::from(text);
+        let pairs = [("begin", "end")];
+        let mut cursor = KeywordBracketCursor::new(&rope, 0, &pairs);
+        assert_eq!(cursor.match_keyword(), Some((33, 36)));
+        let mut cursor = KeywordBracketCursor::new(&rope, 34, &pairs);
+        assert_eq!(cursor.match_keyword(), Some((0, 5)));
+        let mut cursor = KeywordBracketCursor::new(&rope, 8, &pairs);
+        assert_eq!(cursor.match_keyword(), None);
+    }
+
+    #[test]
+    fn boundaries_utf16_should_count_surrogate_pairs() {
+        let rope = Rope::from("😀 word 😀x\n😀 end");
         let mut cursor = WordCursor::new(&rope, 0);
-        let position = cursor.match_pairs();
-        assert_eq!(position, Some(2));
+        assert_eq!(cursor.next_boundary_utf16(), Some((0, 3)));
+        assert_eq!(cursor.inner.pos(), 5);
+        assert_eq!(cursor.next_boundary_utf16(), Some((0, 8)));
+        assert_eq!(cursor.prev_boundary_utf16(), Some((0, 3)));
+
+        // Columns restart at each line
+        let mut cursor = WordCursor::new(&rope, 16);
+        assert_eq!(cursor.next_boundary_utf16(), Some((1, 3)));
     }
 
     #[test]
-    fn should_match_pair_backward() {
-        let text = "{ }";
+    fn bracket_cursor_should_match_custom_pairs() {
+        let rope = Rope::from("«a» ⟨(b)⟩");
+        let pairs = [('«', '»'), ('⟨', '⟩')];
+        let mut cursor = BracketCursor::new(&rope, 0).with_pairs(&pairs);
+        assert_eq!(cursor.match_bracket(), Some(3));
+        assert_eq!(cursor.match_bracket(), Some(0));
+        let mut cursor = BracketCursor::new(&rope, 6).with_pairs(&pairs);
+        assert_eq!(cursor.match_bracket(), Some(12));
+        let mut cursor = BracketCursor::new(&rope, 9).with_pairs(&pairs);
+        assert_eq!(cursor.match_bracket(), Some(11));
+        let mut cursor = BracketCursor::new(&rope, 3).with_pairs(&pairs);
+        assert_eq!(cursor.next_bracket(None), Some(6));
+
+        // Without the custom table these are not brackets
+        let mut cursor = BracketCursor::new(&rope, 0);
+        assert_eq!(cursor.match_bracket(), None);
+    }
+
+    #[test]
+    fn nearest_boundary_should_snap_to_closest_word_edge() {
+        let rope = Rope::from("let value    = 1;");
+        assert_eq!(nearest_boundary(&rope, 5), 4);
+        assert_eq!(nearest_boundary(&rope, 8), 9);
+        assert_eq!(nearest_boundary(&rope, 9), 9);
+        // Ties snap to the left
+        assert_eq!(nearest_boundary(&rope, 11), 9);
+        assert_eq!(nearest_boundary(&rope, 1), 0);
+    }
+
+    #[test]
+    fn bracket_health_should_count_matched_and_stray_brackets() {
+        let pairs = [('(', ')'), ('[', ']'), ('{', '}')];
+        let rope = Rope::from("fn a() { b[0]; }");
+        let health = bracket_health(&rope, 0..rope.len(), &pairs);
+        assert_eq!(health.matched, 3);
+        assert!(health.is_balanced());
+
+        let rope = Rope::from("{ a) } (");
+        let health = bracket_health(&rope, 0..rope.len(), &pairs);
+        assert_eq!(
+            health,
+            BracketHealth {
+                matched: 1,
+                unmatched_open: 1,
+                unmatched_close: 1
+            }
+        );
+        assert!(!health.is_balanced());
+    }
+
+    #[test]
+    fn select_block_of_lines_should_stop_at_blank_lines() {
+        let text = "intro\n\nfirst\n    second\nthird\n  \nlast\nend";
         let rope = Rope::from(text);
-        let mut cursor = WordCursor::new(&rope, 2);
-        let position = cursor.match_pairs();
-        assert_eq!(position, Some(0));
+        let (start, end) = select_block_of_lines(&rope, 15);
+        assert_eq!(&text[start..end], "first\n    second\nthird");
+        // Paragraph at the buffer end
+        let (start, end) = select_block_of_lines(&rope, text.len());
+        assert_eq!(&text[start..end], "last\nend");
+        // On a blank line
+        assert_eq!(select_block_of_lines(&rope, 6), (6, 6));
     }
 
     #[test]
-    fn match_pair_should_be_none() {
-        let text = "{ }";
+    fn prev_deletion_boundary_should_stop_at_subwords_when_enabled() {
+        let text = "let getHTTPResponse = foo_bar";
         let rope = Rope::from(text);
-        let mut cursor = WordCursor::new(&rope, 1);
-        let position = cursor.match_pairs();
-        assert_eq!(position, None);
+        let options = WordOptions {
+            subword_deletion: true,
+            ..Default::default()
+        };
+        let delete = |pos| {
+            WordCursor::with_options(&rope, pos, options)
+                .prev_deletion_boundary()
+                .unwrap()
+        };
+        assert_eq!(&text[..delete(19)], "let getHTTP");
+        assert_eq!(&text[..delete(11)], "let get");
+        assert_eq!(&text[..delete(7)], "let ");
+        assert_eq!(&text[..delete(text.len())], "let getHTTPResponse = foo_");
+
+        let mut cursor = WordCursor::new(&rope, 19);
+        assert_eq!(cursor.prev_deletion_boundary(), Some(4));
     }
 
     #[test]
-    fn select_word_should_return_word_boundaries() {
-        let text = "violet are blue";
+    fn enclosing_block_header_should_return_signature() {
+        let text = concat!(
+            "use a;\n",
+            "\n",
+            "pub fn process<T>(\n",
+            "    value: T,\n",
+            ") -> T\n",
+            "where\n",
+            "    T: Clone,\n",
+            "{\n",
+            "    let b = { value };\n",
+            "    b\n",
+            "}\n",
+        );
         let rope = Rope::from(text);
-        let mut cursor = WordCursor::new(&rope, 9);
-        let (start, end) = cursor.select_word();
-        assert_eq!(&text[start..end], "are");
+        let pos = text.find("    b\n").unwrap();
+        let (start, end) = enclosing_block_header(&rope, pos).unwrap();
+        assert_eq!(
+            &text[start..end],
+            "pub fn process<T>(\n    value: T,\n) -> T\nwhere\n    T: Clone,"
+        );
+
+        let pos = text.find("value }").unwrap();
+        let (start, end) = enclosing_block_header(&rope, pos).unwrap();
+        assert_eq!(&text[start..end], "let b =");
+        assert_eq!(enclosing_block_header(&rope, 0), None);
+
+        // A block without header
+        let rope = Rope::from("a();\n{\n    b\n}");
+        assert_eq!(enclosing_block_header(&rope, 8), None);
     }
 
     #[test]
-    fn should_get_deletion_boundary_backward() {
-        let text = "violet are blue";
+    fn enclosing_block_header_should_stop_at_previous_statement() {
+        let text = "impl A {\n    fn a() {}\n    fn b(&self) {\n        c\n    }\n}";
         let rope = Rope::from(text);
-        let mut cursor = WordCursor::new(&rope, 9);
-        let position = cursor.prev_deletion_boundary();
-        let position = position;
+        let pos = text.find('c').unwrap();
+        let (start, end) = enclosing_block_header(&rope, pos).unwrap();
+        assert_eq!(&text[start..end], "fn b(&self)");
+        let (start, end) = enclosing_block_header(&rope, 21).unwrap();
+        assert_eq!(&text[start..end], "fn a()");
+    }
 
-        assert_eq!(position, Some(7));
-        assert_eq!(&text[..position.unwrap()], "violet ");
+    #[test]
+    fn classified_words_should_tokenize_simple_expressions() {
+        let rope = Rope::from("x = 42 + y");
+        let tokens: Vec<_> = classified_words(&rope, 0..rope.len()).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (0..1, WordKind::Identifier),
+                (1..2, WordKind::Whitespace),
+                (2..3, WordKind::Operator),
+                (3..4, WordKind::Whitespace),
+                (4..6, WordKind::Number),
+                (6..7, WordKind::Whitespace),
+                (7..8, WordKind::Operator),
+                (8..9, WordKind::Whitespace),
+                (9..10, WordKind::Identifier),
+            ]
+        );
+    }
+
+    #[test]
+    fn classified_words_should_handle_decimals_and_brackets() {
+        let text = "f((3.5).max(x_1))";
+        let rope = Rope::from(text);
+        let tokens: Vec<_> = classified_words(&rope, 0..rope.len())
+            .map(|(range, kind)| (&text[range], kind))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                ("f", WordKind::Identifier),
+                ("(", WordKind::Other),
+                ("(", WordKind::Other),
+                ("3.5", WordKind::Number),
+                (")", WordKind::Other),
+                (".", WordKind::Operator),
+                ("max", WordKind::Identifier),
+                ("(", WordKind::Other),
+                ("x_1", WordKind::Identifier),
+                (")", WordKind::Other),
+                (")", WordKind::Other),
+            ]
+        );
+    }
+
+    #[test]
+    fn match_pairs_at_junction_should_prefer_closing_bracket_on_the_left() {
+        let rope = Rope::from("if a {b}{c} f(x)(y)");
+        assert_eq!(match_pairs_at_junction(&rope, 8), Some((5, 7)));
+        assert_eq!(match_pairs_at_junction(&rope, 16), Some((13, 15)));
+        // Only one bracket next to the caret
+        assert_eq!(match_pairs_at_junction(&rope, 5), Some((5, 7)));
+        assert_eq!(match_pairs_at_junction(&rope, 19), Some((16, 18)));
+        assert_eq!(match_pairs_at_junction(&rope, 0), None);
+
+        // An unmatched closing bracket falls back to the right bracket
+        let rope = Rope::from("a){b}");
+        assert_eq!(match_pairs_at_junction(&rope, 2), Some((2, 4)));
+    }
+
+    #[test]
+    fn expand_once_should_walk_the_expansion_chain() {
+        let text = "let a = 1;\nfoo(bar_baz)\n\nend";
+        let rope = Rope::from(text);
+        let mut sel = (16, 16);
+        let mut chain = Vec::new();
+        loop {
+            let next = expand_once(&rope, sel);
+            if next == sel {
+                break;
+            }
+            chain.push(&text[next.0..next.1]);
+            sel = next;
+        }
+        assert_eq!(
+            chain,
+            vec![
+                "bar",
+                "bar_baz",
+                "(bar_baz)",
+                "foo(bar_baz)",
+                "let a = 1;\nfoo(bar_baz)"
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_once_should_go_through_nested_brackets() {
+        let text = "f(a, [b, c])";
+        let rope = Rope::from(text);
+        let expand = |sel| {
+            let (start, end) = expand_once(&rope, sel);
+            &text[start..end]
+        };
+        assert_eq!(expand((6, 7)), "b, c");
+        assert_eq!(expand((6, 10)), "[b, c]");
+        assert_eq!(expand((5, 11)), "a, [b, c]");
+        assert_eq!(expand((2, 11)), "(a, [b, c])");
+    }
+
+    #[test]
+    fn in_string_heuristic_should_use_quote_parity_per_line() {
+        let text = r#"a("x\"y", 'z"') + b"#;
+        let rope = Rope::from(text);
+        let quotes = ['"', '\''];
+        let inside = |pos| in_string_heuristic(&rope, pos, &quotes);
+        assert!(!inside(2));
+        assert!(inside(3));
+        assert!(inside(6));
+        assert!(inside(7));
+        assert!(!inside(8));
+        assert!(inside(12));
+        assert!(inside(13));
+        assert!(!inside(14));
+        // Only double quotes are considered
+        assert!(!in_string_heuristic(&rope, 12, &['"']));
+
+        // Each line starts outside of any string
+        let rope = Rope::from("\"open\n b");
+        assert!(!in_string_heuristic(&rope, 7, &quotes));
+    }
+
+    #[test]
+    fn paragraph_bounds_should_return_surrounding_blank_lines() {
+        let text = "first\nparagraph\n\nsecond\n  one\n\n\nlast";
+        let rope = Rope::from(text);
+        assert_eq!(paragraph_bounds(&rope, 2), (0, 16));
+        assert_eq!(paragraph_bounds(&rope, 20), (16, 30));
+        assert_eq!(&text[16..17], "\n");
+        assert_eq!(&text[30..31], "\n");
+        assert_eq!(paragraph_bounds(&rope, text.len()), (31, text.len()));
+        // On a blank line
+        assert_eq!(paragraph_bounds(&rope, 30), (30, 30));
+    }
+
+    #[test]
+    fn reflow_breaks_should_fit_lines_in_width() {
+        let text = "The quick brown fox\njumps over   the lazy dog";
+        let rope = Rope::from(text);
+        let breaks = reflow_breaks(&rope, 0..rope.len(), 15);
+        // Lines once reflowed: "The quick brown", "fox jumps over", "the lazy dog"
+        assert_eq!(breaks, vec![15, 30]);
+        assert_eq!(&text[..15], "The quick brown");
+        assert_eq!(&text[16..30], "fox\njumps over");
+
+        // A word longer than the width gets its own line
+        let rope = Rope::from("a verylongword b");
+        assert_eq!(reflow_breaks(&rope, 0..rope.len(), 5), vec![1, 14]);
+        assert!(reflow_breaks(&rope, 0..rope.len(), 80).is_empty());
+    }
+
+    #[test]
+    fn bracket_cursor_should_skip_raw_strings() {
+        let text = "fn a() {\n    let s = r#\"}\n\"{\"#;\n    b(\"}\");\n}";
+        let rope = Rope::from(text);
+        let start = text.find("r#").unwrap();
+        let end = text.find("#;").unwrap() + 1;
+        let raw_string = start..end;
+        let raw_strings = std::slice::from_ref(&raw_string);
+        let close = text.len() - 1;
+
+        let mut cursor = BracketCursor::new(&rope, 7)
+            .with_raw_strings(raw_strings)
+            .with_quote_skipping(true);
+        assert_eq!(cursor.match_bracket(), Some(close));
+        let mut cursor = BracketCursor::new(&rope, close)
+            .with_raw_strings(raw_strings)
+            .with_quote_skipping(true);
+        assert_eq!(cursor.match_bracket(), Some(7));
+
+        // A bracket inside a raw string is not matched
+        let mut cursor =
+            BracketCursor::new(&rope, start + 3).with_raw_strings(raw_strings);
+        assert_eq!(cursor.match_bracket(), None);
+    }
+
+    #[test]
+    fn column_selection_should_map_columns_per_line() {
+        let text = "let a = 1;\nif\n\tb = 2;\nc   = 3;";
+        let rope = Rope::from(text);
+        let ranges = column_selection(&rope, 0..4, 4..7, 4);
+        let selected: Vec<_> = ranges.iter().map(|r| &text[r.clone()]).collect();
+        assert_eq!(selected, vec!["a =", "", "b =", "= 3"]);
+        assert_eq!(ranges[1], 13..13);
+
+        // The tab spans columns 0 to 4, it starts before column 2
+        let ranges = column_selection(&rope, 2..3, 2..6, 4);
+        assert_eq!(ranges, vec![15..17]);
+    }
+
+    #[test]
+    fn byte_order_mark_should_not_merge_into_first_word() {
+        let text = "\u{feff}hello world";
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.next_boundary(), Some(3));
+        let mut cursor = WordCursor::new(&rope, 0);
+        assert_eq!(cursor.select_word(), (3, 8));
+        let mut cursor = WordCursor::new(&rope, 5);
+        assert_eq!(cursor.select_word(), (3, 8));
+        assert_eq!(get_char_property('\u{feff}'), CharClassification::Space);
+        assert_eq!(
+            get_unicode_char_property('\u{feff}'),
+            CharClassification::Space
+        );
+    }
+
+    #[test]
+    fn structural_delete_forward_should_keep_brackets_balanced() {
+        let rope = Rope::from("f(ab) g() [x");
+        // Inside a pair
+        assert_eq!(structural_delete_forward(&rope, 2), Some(2..3));
+        assert_eq!(structural_delete_forward(&rope, 3), Some(3..4));
+        // At the edges of a pair, the whole pair is deleted from its opening bracket
+        assert_eq!(structural_delete_forward(&rope, 4), None);
+        assert_eq!(structural_delete_forward(&rope, 1), Some(1..5));
+        // Empty pair
+        assert_eq!(structural_delete_forward(&rope, 7), Some(7..9));
+        // Unmatched opening bracket
+        assert_eq!(structural_delete_forward(&rope, 10), Some(10..11));
+        assert_eq!(structural_delete_forward(&rope, rope.len()), None);
+
+        // Nested pairs are deleted with the outer one
+        let text = "[(a) {b}] c";
+        let rope = Rope::from(text);
+        let range = structural_delete_forward(&rope, 0).unwrap();
+        assert_eq!(&text[range], "[(a) {b}]");
+        assert_eq!(structural_delete_forward(&rope, 5), Some(5..8));
+    }
+
+    #[test]
+    fn match_pairs_result_should_distinguish_each_case() {
+        let rope = Rope::from("f(a) ]");
+        let result = |pos| WordCursor::new(&rope, pos).match_pairs_result();
+        assert_eq!(result(1), MatchResult::Matched(3));
+        assert_eq!(result(3), MatchResult::Matched(1));
+        assert_eq!(result(0), MatchResult::NotOnBracket);
+        assert_eq!(result(rope.len()), MatchResult::NotOnBracket);
+        assert_eq!(result(5), MatchResult::Unmatched);
+        assert_eq!(WordCursor::new(&rope, 5).match_pairs(), None);
+    }
+
+    #[test]
+    fn word_motion_should_stop_at_wrap_points() {
+        // Wrapped at column 8, in the middle of `wrapped`
+        let text = "a long wrapped line\nnext";
+        let rope = Rope::from(text);
+        let wrap_points = [8, 15];
+        assert_eq!(display_row_range(&rope, 0, &wrap_points, 0), Some(0..8));
+        assert_eq!(display_row_range(&rope, 0, &wrap_points, 1), Some(8..15));
+        assert_eq!(display_row_range(&rope, 0, &wrap_points, 2), Some(15..19));
+        assert_eq!(display_row_range(&rope, 0, &wrap_points, 3), None);
+        assert_eq!(display_row_range(&rope, 1, &wrap_points, 0), Some(20..24));
+
+        let options = WordOptions {
+            wrap_points: &wrap_points,
+            ..Default::default()
+        };
+        let mut cursor = WordCursor::with_options(&rope, 2, options);
+        assert_eq!(cursor.next_boundary(), Some(7));
+        assert_eq!(cursor.next_boundary(), Some(8));
+        assert_eq!(cursor.next_boundary(), Some(15));
+        assert_eq!(cursor.prev_boundary(), Some(8));
+        assert_eq!(cursor.prev_boundary(), Some(7));
+    }
+
+    #[test]
+    fn bracket_role_should_tell_opening_and_closing_brackets() {
+        let rope = Rope::from("(a) «b»");
+        let role = |pos| BracketCursor::new(&rope, pos).bracket_role();
+        assert_eq!(role(0), Some(BracketRole::Open));
+        assert_eq!(role(2), Some(BracketRole::Close));
+        assert_eq!(role(1), None);
+        assert_eq!(role(rope.len()), None);
+
+        let pairs = [('«', '»')];
+        let role = |pos| {
+            BracketCursor::new(&rope, pos)
+                .with_pairs(&pairs)
+                .bracket_role()
+        };
+        assert_eq!(role(4), Some(BracketRole::Open));
+        assert_eq!(role(7), Some(BracketRole::Close));
+    }
+
+    #[test]
+    fn indent_for_new_line_should_follow_brackets() {
+        let pairs = [('(', ')'), ('{', '}')];
+        let text = "fn a() {\n    if b {}\n    c\n    }";
+        let rope = Rope::from(text);
+        let indent = |pos| indent_for_new_line(&rope, pos, "    ", &pairs);
+        // After an opening bracket
+        assert_eq!(indent(8), "    ");
+        // Between an empty pair
+        assert_eq!(indent(19), "        ");
+        // Plain line
+        assert_eq!(indent(text.find('c').unwrap() + 1), "    ");
+        // Before a closing bracket
+        assert_eq!(indent(text.len() - 1), "");
+    }
+
+    #[test]
+    fn convert_identifier_should_keep_separated_digits_and_affixes() {
+        let convert = |text: &str, to| {
+            convert_identifier(&Rope::from(text), 0..text.len(), to)
+        };
+        assert_eq!(convert("x86_64", IdentifierStyle::KebabCase), "x86-64");
+        assert_eq!(convert("x86_64", IdentifierStyle::PascalCase), "X86_64");
+        assert_eq!(
+            convert("__init__", IdentifierStyle::ScreamingSnake),
+            "__INIT__"
+        );
+        assert_eq!(
+            convert("_private_name", IdentifierStyle::CamelCase),
+            "_privateName"
+        );
+        assert_eq!(
+            convert("-webkit-box-", IdentifierStyle::SnakeCase),
+            "-webkit_box-"
+        );
+    }
+
+    #[test]
+    fn convert_identifier_to_own_style_should_be_unchanged() {
+        for (text, style) in [
+            ("x86_64", IdentifierStyle::SnakeCase),
+            ("__init__", IdentifierStyle::SnakeCase),
+            ("getHTTPResponse", IdentifierStyle::CamelCase),
+            ("HTTPServer2", IdentifierStyle::PascalCase),
+            ("MAX_U8", IdentifierStyle::ScreamingSnake),
+            ("foo-bar-2", IdentifierStyle::KebabCase),
+        ] {
+            let rope = Rope::from(text);
+            assert_eq!(identifier_style(&rope, 0..text.len()), style);
+            assert_eq!(convert_identifier(&rope, 0..text.len(), style), text);
+        }
+    }
+
+    #[test]
+    fn template_block_cursor_should_match_from_inside_tags_across_text() {
+        let text = "é {% for x in «y» %}\n ü {%if x%}{% endif %}\n{% endfor %} ö";
+        let rope = Rope::from(text);
+        let matched = |pos| {
+            TemplateBlockCursor::new(&rope, pos, ("{%", "%}"), &JINJA_BLOCKS)
+                .match_tag()
+                .map(|(start, end)| &text[start..end])
+        };
+        // Anywhere inside the opening tag, wrapper included
+        assert_eq!(matched(3), Some("{% endfor %}"));
+        assert_eq!(matched(7), Some("{% endfor %}"));
+        assert_eq!(matched(22), Some("{% endfor %}"));
+        let close = text.find("{% endfor").unwrap();
+        assert_eq!(matched(close + 4), Some("{% for x in «y» %}"));
+        // Outside of any tag
+        assert_eq!(matched(0), None);
+        assert_eq!(matched(text.len() - 1), None);
     }
 }